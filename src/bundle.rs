@@ -0,0 +1,334 @@
+use crate::config;
+use crate::domain::{
+    AppConfig, ConfigFormat, GitUserConfig, ImportAction, ImportChange, ImportStrategy,
+    ProfileBundle, SigningPolicy,
+};
+use crate::error::AppError;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Read;
+use std::path::Path;
+
+pub const BUNDLE_VERSION: u64 = 1;
+
+pub fn export(
+    app_config: &AppConfig,
+    names: &[String],
+    strip_signing_keys: bool,
+) -> Result<ProfileBundle, AppError> {
+    let mut pending: Vec<String> = if names.is_empty() {
+        app_config.profiles.keys().cloned().collect()
+    } else {
+        names.to_vec()
+    };
+    let mut profiles = BTreeMap::new();
+    while let Some(name) = pending.pop() {
+        if profiles.contains_key(&name) {
+            continue;
+        }
+        let mut profile = app_config
+            .profiles
+            .get(&name)
+            .ok_or_else(|| AppError::ProfileNotFound(name.clone()))?
+            .clone();
+        if let Some(parent) = &profile.extends {
+            pending.push(parent.clone());
+        }
+        if strip_signing_keys {
+            profile.signing_key = None;
+            profile.signing_format = None;
+            profile.signing_program = None;
+            // Without a key, only 'always' would fail; 'never' and 'untouched'
+            // still mean the same on the importing machine.
+            if profile.signing_policy == Some(SigningPolicy::Always) {
+                profile.signing_policy = None;
+            }
+        }
+        profiles.insert(name, profile);
+    }
+    Ok(ProfileBundle {
+        bundle_version: BUNDLE_VERSION,
+        profiles,
+    })
+}
+
+pub fn read(path: &Path) -> Result<ProfileBundle, AppError> {
+    let content = if path == Path::new("-") {
+        let mut content = String::new();
+        std::io::stdin().read_to_string(&mut content)?;
+        content
+    } else {
+        std::fs::read_to_string(path)?
+    };
+    let value = config::parse_config_value(ConfigFormat::from_path(path), &content).map_err(
+        |e| match e {
+            AppError::ConfigFormatError { message, .. } => AppError::InvalidBundle(message),
+            e => e,
+        },
+    )?;
+    let bundle: ProfileBundle =
+        serde_json::from_value(value).map_err(|e| AppError::InvalidBundle(e.to_string()))?;
+    if bundle.bundle_version > BUNDLE_VERSION {
+        return Err(AppError::InvalidBundle(format!(
+            "bundle version {} is newer than this version of gitup supports",
+            bundle.bundle_version
+        )));
+    }
+    Ok(bundle)
+}
+
+fn rebase(profile: &GitUserConfig, renames: &BTreeMap<String, String>) -> GitUserConfig {
+    let mut profile = profile.clone();
+    if let Some(parent) = profile
+        .extends
+        .as_ref()
+        .and_then(|parent| renames.get(parent))
+    {
+        profile.extends = Some(parent.clone());
+    }
+    profile
+}
+
+/// A profile that extends a renamed profile changes too, so renames are
+/// repeated until no new conflicts appear.
+pub fn plan(
+    app_config: &AppConfig,
+    bundle: &ProfileBundle,
+    strategy: ImportStrategy,
+) -> Vec<ImportChange> {
+    let mut taken: BTreeSet<String> = app_config
+        .profiles
+        .keys()
+        .chain(bundle.profiles.keys())
+        .cloned()
+        .collect();
+    let mut renames = BTreeMap::new();
+    loop {
+        let changes: Vec<ImportChange> = bundle
+            .profiles
+            .iter()
+            .map(|(name, profile)| {
+                let action = match app_config.profiles.get(name) {
+                    _ if renames.contains_key(name) => ImportAction::Rename,
+                    None => ImportAction::Add,
+                    Some(existing) if *existing == rebase(profile, &renames) => {
+                        ImportAction::Unchanged
+                    }
+                    Some(_) => match strategy {
+                        ImportStrategy::Skip => ImportAction::Skip,
+                        ImportStrategy::Overwrite => ImportAction::Overwrite,
+                        ImportStrategy::Rename => ImportAction::Rename,
+                    },
+                };
+                ImportChange {
+                    profile: name.clone(),
+                    action,
+                    target: renames.get(name).unwrap_or(name).clone(),
+                }
+            })
+            .collect();
+
+        let conflicts: Vec<&ImportChange> = changes
+            .iter()
+            .filter(|change| {
+                change.action == ImportAction::Rename && !renames.contains_key(&change.profile)
+            })
+            .collect();
+        if conflicts.is_empty() {
+            return changes;
+        }
+        for change in conflicts {
+            let target = (2..)
+                .map(|n| format!("{}-{}", change.profile, n))
+                .find(|candidate| !taken.contains(candidate))
+                .unwrap_or_default();
+            taken.insert(target.clone());
+            renames.insert(change.profile.clone(), target);
+        }
+    }
+}
+
+/// Fails before anything is written on a missing parent or a cycle.
+pub fn apply(
+    app_config: &mut AppConfig,
+    bundle: &ProfileBundle,
+    changes: &[ImportChange],
+) -> Result<(), AppError> {
+    let renames: BTreeMap<String, String> = changes
+        .iter()
+        .filter(|change| change.action == ImportAction::Rename)
+        .map(|change| (change.profile.clone(), change.target.clone()))
+        .collect();
+    let imported: Vec<&ImportChange> = changes
+        .iter()
+        .filter(|change| change.action.writes())
+        .collect();
+
+    for change in &imported {
+        let profile = rebase(&bundle.profiles[&change.profile], &renames);
+        app_config.profiles.insert(change.target.clone(), profile);
+    }
+    for change in &imported {
+        config::resolve_profile(app_config, &change.target)?;
+    }
+    for change in &imported {
+        let profile = app_config.profiles[&change.target].clone();
+        config::store_profile(app_config, &change.target, &profile)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(email: &str, extends: Option<&str>) -> GitUserConfig {
+        GitUserConfig {
+            email: Some(email.to_string()),
+            extends: extends.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    fn bundle(profiles: &[(&str, GitUserConfig)]) -> ProfileBundle {
+        ProfileBundle {
+            bundle_version: BUNDLE_VERSION,
+            profiles: profiles
+                .iter()
+                .map(|(name, profile)| (name.to_string(), profile.clone()))
+                .collect(),
+        }
+    }
+
+    fn existing(profiles: &[(&str, GitUserConfig)]) -> AppConfig {
+        let mut app_config = AppConfig::default();
+        for (name, profile) in profiles {
+            app_config
+                .profiles
+                .insert(name.to_string(), profile.clone());
+        }
+        app_config
+    }
+
+    fn summary(changes: &[ImportChange]) -> Vec<(&str, ImportAction, &str)> {
+        changes
+            .iter()
+            .map(|change| {
+                (
+                    change.profile.as_str(),
+                    change.action,
+                    change.target.as_str(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn conflicts_follow_the_strategy() {
+        let app_config = existing(&[
+            ("same", profile("same@x.org", None)),
+            ("taken", profile("old@x.org", None)),
+        ]);
+        let bundle = bundle(&[
+            ("new", profile("new@x.org", None)),
+            ("same", profile("same@x.org", None)),
+            ("taken", profile("other@x.org", None)),
+        ]);
+        for (strategy, action, target) in [
+            (ImportStrategy::Skip, ImportAction::Skip, "taken"),
+            (ImportStrategy::Overwrite, ImportAction::Overwrite, "taken"),
+            (ImportStrategy::Rename, ImportAction::Rename, "taken-2"),
+        ] {
+            assert_eq!(
+                summary(&plan(&app_config, &bundle, strategy)),
+                vec![
+                    ("new", ImportAction::Add, "new"),
+                    ("same", ImportAction::Unchanged, "same"),
+                    ("taken", action, target),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn renamed_names_skip_taken_ones() {
+        let app_config = existing(&[
+            ("work", profile("old@x.org", None)),
+            ("work-2", profile("old2@x.org", None)),
+        ]);
+        let bundle = bundle(&[
+            ("work", profile("new@x.org", None)),
+            ("work-3", profile("new3@x.org", None)),
+        ]);
+        assert_eq!(
+            summary(&plan(&app_config, &bundle, ImportStrategy::Rename)),
+            vec![
+                ("work", ImportAction::Rename, "work-4"),
+                ("work-3", ImportAction::Add, "work-3"),
+            ]
+        );
+    }
+
+    #[test]
+    fn children_of_renamed_profiles_are_renamed_too() {
+        // 'child' is identical to the existing one until its parent is
+        // renamed, after which it would extend a different profile.
+        let app_config = existing(&[
+            ("base", profile("old@x.org", None)),
+            ("child", profile("child@x.org", Some("base"))),
+            ("grandchild", profile("grand@x.org", Some("child"))),
+        ]);
+        let bundle = bundle(&[
+            ("base", profile("new@x.org", None)),
+            ("child", profile("child@x.org", Some("base"))),
+            ("grandchild", profile("grand@x.org", Some("child"))),
+        ]);
+        let changes = plan(&app_config, &bundle, ImportStrategy::Rename);
+        assert_eq!(
+            summary(&changes),
+            vec![
+                ("base", ImportAction::Rename, "base-2"),
+                ("child", ImportAction::Rename, "child-2"),
+                ("grandchild", ImportAction::Rename, "grandchild-2"),
+            ]
+        );
+
+        let renames: BTreeMap<String, String> = changes
+            .iter()
+            .map(|change| (change.profile.clone(), change.target.clone()))
+            .collect();
+        let rebased = |name: &str| rebase(&bundle.profiles[name], &renames).extends;
+        assert_eq!(rebased("child").as_deref(), Some("base-2"));
+        assert_eq!(rebased("grandchild").as_deref(), Some("child-2"));
+    }
+
+    #[test]
+    fn stripping_keys_keeps_policies_that_need_no_key() {
+        let mut app_config = AppConfig::default();
+        for (name, policy) in [
+            ("always", SigningPolicy::Always),
+            ("never", SigningPolicy::Never),
+            ("untouched", SigningPolicy::Untouched),
+        ] {
+            let profile = GitUserConfig {
+                signing_key: Some("ABCDEF0123456789".to_string()),
+                signing_policy: Some(policy),
+                ..profile("me@x.org", None)
+            };
+            app_config.profiles.insert(name.to_string(), profile);
+        }
+        let bundle = export(&app_config, &[], true).unwrap();
+        let policies: Vec<_> = bundle
+            .profiles
+            .values()
+            .map(|profile| (profile.signing_key.as_deref(), profile.signing_policy))
+            .collect();
+        assert_eq!(
+            policies,
+            vec![
+                (None, None),
+                (None, Some(SigningPolicy::Never)),
+                (None, Some(SigningPolicy::Untouched)),
+            ]
+        );
+    }
+}
@@ -1,5 +1,7 @@
+use crate::domain::{ConfigFormat, ImportStrategy, SigningFormat, SigningPolicy};
 use clap::{Parser, Subcommand};
 use clap_complete::Shell;
+use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -10,6 +12,21 @@ pub struct Cli {
 
     #[arg(short, long, global = true, help = "Output in JSON format")]
     pub json: bool,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "PATH",
+        help = "Use this GitUp config file instead of the default. Overrides GITUP_CONFIG."
+    )]
+    pub config: Option<PathBuf>,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Show the changes a command would make without making them."
+    )]
+    pub dry_run: bool,
 }
 
 #[derive(Subcommand, Debug)]
@@ -21,7 +38,22 @@ pub enum Commands {
         about = "Checks the current Git installation and configuration.",
         alias = "status"
     )]
-    Check,
+    Check {
+        #[arg(long, help = "Show only the values set in the current repository.")]
+        local: bool,
+    },
+
+    #[command(about = "Runs a full diagnostic of the Git installation and GitUp configuration.")]
+    Doctor,
+
+    #[command(about = "Prints the files GitUp reads and writes.")]
+    Paths,
+
+    #[command(about = "Manages the GitUp config file itself.")]
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
 
     #[command(about = "Sets Git configuration values directly.")]
     Set(ConfigArgs),
@@ -30,21 +62,77 @@ pub enum Commands {
     Save {
         #[arg(help = "The name for the new profile.")]
         name: String,
+
+        #[arg(
+            short,
+            long = "key",
+            value_name = "KEY",
+            help = "An extra Git config key to capture, e.g. core.sshCommand. Can be repeated."
+        )]
+        keys: Vec<String>,
+
+        #[arg(
+            long,
+            value_name = "PROFILE",
+            help = "Inherit from another profile and store only the values that differ from it."
+        )]
+        extends: Option<String>,
+    },
+
+    #[command(about = "Shows the values stored in a profile.")]
+    Show {
+        #[arg(help = "The name of the profile to show.")]
+        name: String,
+
+        #[arg(
+            long,
+            help = "Merge in inherited values and show which profile each one comes from."
+        )]
+        resolved: bool,
+    },
+
+    #[command(
+        about = "Compares two profiles, or a profile with the live global Git config. Exits with 1 if they differ and 2 on error."
+    )]
+    Diff {
+        #[arg(help = "The profile to compare.")]
+        profile: String,
+
+        #[arg(help = "The profile to compare it with. Defaults to the live global Git config.")]
+        other: Option<String>,
     },
 
     #[command(about = "Switches the global Git config to a saved profile.")]
     Use {
         #[arg(
-            help = "The name of the profile to use. If omitted, an interactive selector will be shown."
+            help = "The name of the profile to use, or '-' for the previous one. If omitted, an interactive selector will be shown."
         )]
         name: Option<String>,
+
+        #[arg(long, help = "Apply the profile to the current repository only.")]
+        local: bool,
     },
 
     #[command(about = "Lists all saved profiles.", alias = "ls")]
     List,
 
     #[command(about = "Shows which profile is currently active.", alias = "active")]
-    Current,
+    Current {
+        #[arg(long, help = "Find the saved profiles that match the live Git config.")]
+        detect: bool,
+    },
+
+    #[command(about = "Lists earlier global identities that 'undo' can restore.")]
+    History,
+
+    #[command(about = "Restores the global identity from before a recent change.")]
+    Undo {
+        #[arg(
+            default_value_t = 1,
+            help = "The history entry to restore, as numbered by 'gitup history'."
+        )]
+        n: usize,
+    },
 
     #[command(about = "Deletes a saved profile.", alias = "rm")]
     Delete {
@@ -55,17 +143,251 @@ pub enum Commands {
         force: bool,
     },
 
+    #[command(about = "Renames a saved profile.", alias = "mv")]
+    Rename {
+        #[arg(help = "The current name of the profile.")]
+        old: String,
+
+        #[arg(help = "The new name for the profile.")]
+        new: String,
+    },
+
+    #[command(about = "Copies a saved profile under a new name.", alias = "cp")]
+    Copy {
+        #[arg(help = "The name of the profile to copy.")]
+        source: String,
+
+        #[arg(help = "The name for the copy.")]
+        destination: String,
+    },
+
+    #[command(about = "Changes values stored in a saved profile.")]
+    Edit(EditArgs),
+
+    #[command(about = "Writes profiles to standard output as a bundle for 'gitup import'.")]
+    Export {
+        #[arg(
+            help = "The profiles to export, along with the profiles they extend. Exports all profiles if omitted."
+        )]
+        names: Vec<String>,
+
+        #[arg(long, help = "Leave signing keys out of the exported profiles.")]
+        strip_signing_keys: bool,
+    },
+
+    #[command(about = "Adds the profiles from a bundle written by 'gitup export'.")]
+    Import {
+        #[arg(help = "The bundle file to import, or '-' to read standard input.")]
+        file: PathBuf,
+
+        #[arg(
+            short,
+            long,
+            value_enum,
+            default_value = "skip",
+            help = "What to do with profiles whose name is already taken."
+        )]
+        strategy: ImportStrategy,
+
+        #[arg(long, help = "Only show what would change.")]
+        preview: bool,
+
+        #[arg(short, long, help = "Bypass the confirmation prompt.")]
+        yes: bool,
+    },
+
+    #[command(about = "Binds a directory to a profile for every repository beneath it.")]
+    Bind {
+        #[arg(help = "The directory whose repositories should use the profile.")]
+        dir: PathBuf,
+
+        #[arg(help = "The name of the profile to bind.")]
+        profile: String,
+    },
+
+    #[command(about = "Removes a directory binding.")]
+    Unbind {
+        #[arg(help = "The directory to unbind.")]
+        dir: PathBuf,
+    },
+
+    #[command(about = "Manages profile rules based on repository remote URLs.")]
+    Rules {
+        #[command(subcommand)]
+        action: RulesCommand,
+    },
+
+    #[command(about = "Manages the pre-commit hook that blocks commits with the wrong identity.")]
+    Guard {
+        #[command(subcommand)]
+        action: GuardCommand,
+    },
+
     #[command(about = "Generates shell completion scripts.")]
     Completions {
         #[arg(value_enum, help = "The shell to generate completions for.")]
         shell: Shell,
     },
+
+    #[command(
+        about = "Prints a shell hook that activates the matching profile on directory change."
+    )]
+    Hook {
+        #[arg(
+            value_enum,
+            help = "The shell to generate the hook for (bash, zsh or fish)."
+        )]
+        shell: Shell,
+    },
+
+    #[command(
+        about = "Prints the environment for the profile matching the current directory.",
+        hide = true
+    )]
+    Env {
+        #[arg(value_enum, help = "The shell to print the environment for.")]
+        shell: Shell,
+    },
+}
+
+impl Commands {
+    pub fn supports_dry_run(&self) -> bool {
+        matches!(
+            self,
+            Commands::Setup(_)
+                | Commands::Set(_)
+                | Commands::Save { .. }
+                | Commands::Use { .. }
+                | Commands::Delete { .. }
+        )
+    }
+
+    /// `diff` follows diff(1), where 1 means the inputs differ.
+    pub fn failure_code(&self) -> i32 {
+        match self {
+            Commands::Diff { .. } => 2,
+            _ => 1,
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum RulesCommand {
+    #[command(about = "Uses a profile for repositories whose remote URL matches a pattern.")]
+    Add {
+        #[arg(help = "The remote URL pattern, e.g. 'git@github.com:acme-corp/**'.")]
+        pattern: String,
+
+        #[arg(help = "The name of the profile to use.")]
+        profile: String,
+    },
+
+    #[command(about = "Lists all remote rules.", alias = "ls")]
+    List,
+
+    #[command(about = "Removes a remote rule.", alias = "rm")]
+    Remove {
+        #[arg(help = "The remote URL pattern of the rule to remove.")]
+        pattern: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    #[command(about = "Rewrites the GitUp config file in another format.")]
+    Convert {
+        #[arg(long, value_enum, help = "The format to convert to.")]
+        to: ConfigFormat,
+    },
+
+    #[command(about = "Stores each profile in its own file in profiles.d.")]
+    Split,
+
+    #[command(about = "Moves the profiles in profiles.d back into the config file.")]
+    Join,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum GuardCommand {
+    #[command(about = "Installs the guard as the repository's pre-commit hook.")]
+    Install {
+        #[arg(
+            short,
+            long,
+            help = "Pin the profile this repository expects instead of resolving it from bindings and rules."
+        )]
+        profile: Option<String>,
+    },
+
+    #[command(about = "Removes the guard and restores any chained hook.")]
+    Uninstall,
+
+    #[command(about = "Verifies that the author identity matches the expected profile.")]
+    Check,
 }
 
 #[derive(Parser, Debug)]
 pub struct SetupArgs {
     #[arg(long, help = "Run non-interactively, fails if input is required.")]
     pub non_interactive: bool,
+
+    #[arg(long, help = "Accept the entered values without validating them.")]
+    pub force: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct EditArgs {
+    #[arg(help = "The name of the profile to edit.")]
+    pub profile: String,
+
+    #[arg(short, long, help = "The user name to store.")]
+    pub name: Option<String>,
+
+    #[arg(short, long, help = "The user email to store.")]
+    pub email: Option<String>,
+
+    #[arg(
+        short = 's',
+        long,
+        help = "The GPG/SSH signing key to store. Use an empty string to unset."
+    )]
+    pub signing_key: Option<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "The signing key format. Detected from the signing key when omitted."
+    )]
+    pub signing_format: Option<SigningFormat>,
+
+    #[arg(
+        long,
+        help = "The program Git uses to sign with this format. Use an empty string to unset."
+    )]
+    pub signing_program: Option<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Whether commits, tags and pushes are signed."
+    )]
+    pub signing_policy: Option<SigningPolicy>,
+
+    #[arg(
+        long,
+        help = "The SSH private key to store. Use an empty string to unset."
+    )]
+    pub ssh_key: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PROFILE",
+        help = "The profile to inherit from. Use an empty string to stop inheriting."
+    )]
+    pub extends: Option<String>,
+
+    #[arg(long, help = "Skip validation and write the values as given.")]
+    pub force: bool,
 }
 
 #[derive(Parser, Debug)]
@@ -93,4 +415,40 @@ pub struct ConfigArgs {
         help = "The GPG/SSH signing key to use. Use an empty string to unset."
     )]
     pub signing_key: Option<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "The signing key format. Detected from the signing key when omitted."
+    )]
+    pub signing_format: Option<SigningFormat>,
+
+    #[arg(
+        long,
+        help = "The program Git uses to sign with this format, e.g. an SSH agent signer. Use an empty string to unset."
+    )]
+    pub signing_program: Option<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Whether commits, tags and pushes are signed. Defaults to always when a signing key is set."
+    )]
+    pub signing_policy: Option<SigningPolicy>,
+
+    #[arg(
+        long,
+        env = "GITUP_SSH_KEY",
+        help = "The SSH private key to use for Git operations. Use an empty string to unset."
+    )]
+    pub ssh_key: Option<String>,
+
+    #[arg(
+        long,
+        help = "Write to the current repository's config instead of the global one."
+    )]
+    pub local: bool,
+
+    #[arg(long, help = "Skip validation and write the values as given.")]
+    pub force: bool,
 }
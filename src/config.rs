@@ -1,88 +1,2310 @@
-use crate::domain::{AppConfig, GitUserConfig};
+use crate::domain::{
+    AppConfig, AppState, ConfigFormat, ConfigScope, ConfigSource, FieldComparison, FieldDiff,
+    GitUserConfig, HistoryEntry, PlannedChange, ProfileStatus, SigningFormat, SigningPolicy,
+};
 use crate::error::AppError;
-use crate::platform;
-use std::collections::HashMap;
+use crate::{migrate, platform, ui};
+use fs4::fs_std::FileExt;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ffi::OsString;
 use std::fs;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use toml_edit::{DocumentMut, Item, Table, Value};
 
-fn get_app_config_path() -> Result<PathBuf, AppError> {
-    let home_dir = dirs::home_dir().ok_or(AppError::HomeDirectoryNotFound)?;
-    Ok(home_dir.join(".config").join("gitup").join("config.json"))
+const CONFIG_FILE_STEM: &str = "config";
+const PROFILES_DIR_NAME: &str = "profiles.d";
+const STATE_FILE_NAME: &str = "state.json";
+const HISTORY_FILE_NAME: &str = "history.json";
+
+const HISTORY_LIMIT: usize = 50;
+
+fn find_config_file(dir: &Path) -> Option<PathBuf> {
+    ConfigFormat::EXTENSIONS
+        .iter()
+        .map(|(extension, _)| dir.join(CONFIG_FILE_STEM).with_extension(extension))
+        .find(|path| path.exists())
 }
 
-pub fn load_app_config() -> Result<AppConfig, AppError> {
+fn config_file_in(dir: &Path) -> PathBuf {
+    find_config_file(dir).unwrap_or_else(|| {
+        dir.join(CONFIG_FILE_STEM)
+            .with_extension(ConfigFormat::Json.extension())
+    })
+}
+
+static CONFIG_PATH_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+pub fn set_app_config_path(path: &Path) -> Result<(), AppError> {
+    let path = std::path::absolute(platform::expand_home(path))?;
+    CONFIG_PATH_OVERRIDE.set(path).ok();
+    Ok(())
+}
+
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+static PLANNED_CHANGES: Mutex<Vec<PlannedChange>> = Mutex::new(Vec::new());
+
+pub fn set_dry_run() {
+    DRY_RUN.store(true, Ordering::Relaxed);
+}
+
+pub fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+fn same_planned_target(a: &PlannedChange, b: &PlannedChange) -> bool {
+    match (a, b) {
+        (
+            PlannedChange::GitConfig { scope, key, .. },
+            PlannedChange::GitConfig {
+                scope: other_scope,
+                key: other_key,
+                ..
+            },
+        ) => scope == other_scope && key.eq_ignore_ascii_case(other_key),
+        (PlannedChange::File { path, .. }, PlannedChange::File { path: other, .. }) => {
+            path == other
+        }
+        _ => a == b,
+    }
+}
+
+/// Dry runs never write, so later steps see the same old state and plan the
+/// same change again. Changes to one target are merged into its first entry.
+fn record_planned_change(mut change: PlannedChange) {
+    let mut planned = PLANNED_CHANGES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let index = planned
+        .iter()
+        .position(|earlier| same_planned_target(earlier, &change));
+    if let Some(index) = index {
+        if let (
+            PlannedChange::GitConfig { old, .. } | PlannedChange::File { old, .. },
+            PlannedChange::GitConfig { old: first, .. } | PlannedChange::File { old: first, .. },
+        ) = (&mut change, &planned[index])
+        {
+            *old = first.clone();
+        }
+    }
+    let unchanged = match &change {
+        PlannedChange::GitConfig { old, new, .. } | PlannedChange::File { old, new, .. } => {
+            old == new
+        }
+        PlannedChange::RemoveSection { .. } => false,
+    };
+    match index {
+        Some(index) if unchanged => {
+            planned.remove(index);
+        }
+        Some(index) => planned[index] = change,
+        None if !unchanged => planned.push(change),
+        None => {}
+    }
+}
+
+pub fn take_planned_changes() -> Vec<PlannedChange> {
+    std::mem::take(
+        &mut *PLANNED_CHANGES
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()),
+    )
+}
+
+pub fn resolve_app_config_path() -> Result<(PathBuf, ConfigSource), AppError> {
+    resolve_config_path_from(
+        CONFIG_PATH_OVERRIDE.get().map(PathBuf::as_path),
+        |name| std::env::var_os(name),
+        dirs::home_dir(),
+        dirs::config_dir(),
+    )
+}
+
+/// `--config`, then `GITUP_CONFIG`, then `$XDG_CONFIG_HOME/gitup`, then the
+/// platform directory. A file left in `~/.config/gitup` is used until then.
+fn resolve_config_path_from(
+    flag: Option<&Path>,
+    var: impl Fn(&str) -> Option<OsString>,
+    home_dir: Option<PathBuf>,
+    config_dir: Option<PathBuf>,
+) -> Result<(PathBuf, ConfigSource), AppError> {
+    if let Some(path) = flag {
+        return Ok((path.to_path_buf(), ConfigSource::Flag));
+    }
+    if let Some(path) = var("GITUP_CONFIG").filter(|path| !path.is_empty()) {
+        let path = std::path::absolute(platform::expand_home(Path::new(&path)))?;
+        return Ok((path, ConfigSource::Environment));
+    }
+    if let Some(dir) = var("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+    {
+        return Ok((config_file_in(&dir.join("gitup")), ConfigSource::Xdg));
+    }
+
+    let legacy_dir = home_dir
+        .ok_or(AppError::HomeDirectoryNotFound)?
+        .join(".config")
+        .join("gitup");
+    let legacy = find_config_file(&legacy_dir);
+    if let Some(dir) = config_dir {
+        let path = config_file_in(&dir.join("gitup"));
+        if path.exists() || legacy.is_none() {
+            return Ok((path, ConfigSource::Platform));
+        }
+    }
+    Ok((
+        legacy.unwrap_or_else(|| config_file_in(&legacy_dir)),
+        ConfigSource::Legacy,
+    ))
+}
+
+pub fn get_app_config_path() -> Result<PathBuf, AppError> {
+    resolve_app_config_path().map(|(path, _)| path)
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn get_app_config_dir() -> Result<PathBuf, AppError> {
     let path = get_app_config_path()?;
+    Ok(path.parent().map(Path::to_path_buf).unwrap_or_default())
+}
+
+/// Independent of the format, so `config convert` leaves no stale lock.
+fn lock_path_for(config_path: &Path) -> PathBuf {
+    config_path.with_extension("lock")
+}
+
+pub fn get_lock_path() -> Result<PathBuf, AppError> {
+    Ok(lock_path_for(&get_app_config_path()?))
+}
+
+pub fn get_includes_dir() -> Result<PathBuf, AppError> {
+    Ok(get_app_config_dir()?.join("includes"))
+}
+
+fn profiles_dir_for(config_path: &Path) -> PathBuf {
+    config_path
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join(PROFILES_DIR_NAME)
+}
+
+fn state_path_for(config_path: &Path) -> PathBuf {
+    config_path
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join(STATE_FILE_NAME)
+}
+
+pub fn get_profiles_dir() -> Result<PathBuf, AppError> {
+    Ok(profiles_dir_for(&get_app_config_path()?))
+}
+
+pub fn get_state_path() -> Result<PathBuf, AppError> {
+    Ok(state_path_for(&get_app_config_path()?))
+}
+
+pub fn get_history_path() -> Result<PathBuf, AppError> {
+    Ok(get_app_config_dir()?.join(HISTORY_FILE_NAME))
+}
+
+pub fn get_system_profiles_dir() -> PathBuf {
+    std::env::var_os("GITUP_SYSTEM_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(platform::system_config_dir)
+        .join(PROFILES_DIR_NAME)
+}
+
+pub fn get_global_git_config_path() -> Result<PathBuf, AppError> {
+    if let Some(path) = std::env::var_os("GIT_CONFIG_GLOBAL").filter(|path| !path.is_empty()) {
+        return Ok(PathBuf::from(path));
+    }
+    let home_dir = dirs::home_dir().ok_or(AppError::HomeDirectoryNotFound)?;
+    let dotfile = home_dir.join(".gitconfig");
+    let xdg = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .unwrap_or_else(|| home_dir.join(".config"))
+        .join("git")
+        .join("config");
+    if !dotfile.exists() && xdg.exists() {
+        Ok(xdg)
+    } else {
+        Ok(dotfile)
+    }
+}
+
+fn get_profile_include_path(name: &str) -> Result<PathBuf, AppError> {
+    let file_name = format!("{}.gitconfig", name.replace(['/', '\\'], "_"));
+    Ok(get_includes_dir()?.join(file_name))
+}
+
+struct PendingBackup {
+    path: PathBuf,
+    content: String,
+    notes: Vec<String>,
+}
+
+fn write_backup(backup: &PendingBackup) -> Result<(), AppError> {
+    write_private_file(&backup.path, &backup.content)?;
+    if !is_dry_run() {
+        for note in &backup.notes {
+            ui::print_warning(note);
+        }
+    }
+    Ok(())
+}
+
+pub fn parse_config_value(
+    format: ConfigFormat,
+    content: &str,
+) -> Result<serde_json::Value, AppError> {
+    let parsed = match format {
+        ConfigFormat::Json => serde_json::from_str(content).map_err(|e| e.to_string()),
+        ConfigFormat::Toml => toml::from_str(content).map_err(|e| e.to_string()),
+        ConfigFormat::Yaml => serde_norway::from_str(content).map_err(|e| e.to_string()),
+    };
+    parsed.map_err(|message| AppError::ConfigFormatError {
+        format: format.as_str(),
+        message,
+    })
+}
+
+fn serialize_config<T: Serialize>(format: ConfigFormat, config: &T) -> Result<String, AppError> {
+    let serialized = match format {
+        ConfigFormat::Json => serde_json::to_string_pretty(config).map_err(|e| e.to_string()),
+        ConfigFormat::Toml => toml::to_string_pretty(config).map_err(|e| e.to_string()),
+        ConfigFormat::Yaml => serde_norway::to_string(config).map_err(|e| e.to_string()),
+    };
+    serialized.map_err(|message| AppError::ConfigFormatError {
+        format: format.as_str(),
+        message,
+    })
+}
+
+/// Existing TOML files are edited in place to keep their comments.
+fn serialize_for_file<T: Serialize>(path: &Path, value: &T) -> Result<String, AppError> {
+    let format = ConfigFormat::from_path(path);
+    let content = serialize_config(format, value)?;
+    if format != ConfigFormat::Toml {
+        return Ok(content);
+    }
+    let existing = fs::read_to_string(path)
+        .ok()
+        .and_then(|existing| existing.parse::<DocumentMut>().ok());
+    let (Some(mut document), Ok(updated)) = (existing, content.parse::<DocumentMut>()) else {
+        return Ok(content);
+    };
+    let next_position = document.as_table().len() + updated.as_table().len();
+    merge_toml_table(document.as_table_mut(), updated.as_table(), next_position);
+    Ok(document.to_string())
+}
+
+fn merge_toml_table(target: &mut Table, source: &Table, next_position: usize) {
+    let removed: Vec<String> = target
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .filter(|key| !source.contains_key(key))
+        .collect();
+    for key in removed {
+        target.remove(&key);
+    }
+    for (key, item) in source.iter() {
+        match (target.get_mut(key), item) {
+            (Some(Item::Table(existing)), Item::Table(table)) => {
+                merge_toml_table(existing, table, next_position)
+            }
+            (Some(Item::Value(existing)), Item::Value(value)) => {
+                if without_decor(existing) != without_decor(value) {
+                    let decor = existing.decor().clone();
+                    *existing = value.clone();
+                    *existing.decor_mut() = decor;
+                }
+            }
+            _ => {
+                let mut item = item.clone();
+                // Tables keep their position from the freshly serialized file,
+                // which would interleave them with the existing ones.
+                if let Item::Table(table) = &mut item {
+                    move_to_end(table, next_position);
+                }
+                target.insert(key, item);
+            }
+        }
+    }
+}
+
+fn without_decor(value: &Value) -> String {
+    let mut value = value.clone();
+    value.decor_mut().clear();
+    value.to_string()
+}
+
+fn move_to_end(table: &mut Table, position: usize) {
+    table.set_position(position);
+    for (_, item) in table.iter_mut() {
+        if let Item::Table(table) = item {
+            move_to_end(table, position);
+        }
+    }
+}
+
+pub fn parse_app_config(
+    path: &Path,
+    content: &str,
+) -> Result<(AppConfig, u64, Vec<String>), AppError> {
+    let mut value = parse_config_value(ConfigFormat::from_path(path), content)?;
+    let mut notes = Vec::new();
+    let version = migrate::migrate(&mut value, &mut notes)?;
+    Ok((serde_json::from_value(value)?, version, notes))
+}
+
+fn read_app_config(path: &Path) -> Result<(AppConfig, Option<PendingBackup>), AppError> {
     if !path.exists() {
-        return Ok(AppConfig::default());
+        let mut app_config = AppConfig::default();
+        read_external_profiles(path, &mut app_config)?;
+        return Ok((app_config, None));
     }
     let content = fs::read_to_string(path).map_err(AppError::GlobalConfigError)?;
-    serde_json::from_str(&content).map_err(AppError::SerializationError)
+    let (mut app_config, version, notes) = parse_app_config(path, &content)?;
+    read_external_profiles(path, &mut app_config)?;
+    let backup = (version < migrate::CURRENT_SCHEMA_VERSION).then(|| PendingBackup {
+        path: with_suffix(path, &format!(".v{}.bak", version)),
+        content,
+        notes,
+    });
+    Ok((app_config, backup))
 }
 
-pub fn save_app_config(config: &AppConfig) -> Result<(), AppError> {
-    let path = get_app_config_path()?;
+fn profile_name_for_file(path: &Path) -> Option<String> {
+    let extension = path.extension()?.to_string_lossy().to_lowercase();
+    if !ConfigFormat::EXTENSIONS
+        .iter()
+        .any(|(candidate, _)| *candidate == extension)
+    {
+        return None;
+    }
+    let name = path.file_stem()?.to_string_lossy().into_owned();
+    (!name.starts_with('.')).then_some(name)
+}
+
+fn profile_file_name(name: &str, format: ConfigFormat) -> Result<String, AppError> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        return Err(AppError::InvalidProfileFileName(name.to_string()));
+    }
+    Ok(format!("{}.{}", name, format.extension()))
+}
+
+fn read_profile_files(dir: &Path) -> Result<BTreeMap<String, (PathBuf, GitUserConfig)>, AppError> {
+    let mut profiles = BTreeMap::new();
+    for entry in fs::read_dir(dir).map_err(AppError::GlobalConfigError)? {
+        let path = entry.map_err(AppError::GlobalConfigError)?.path();
+        let Some(name) = profile_name_for_file(&path) else {
+            continue;
+        };
+        let invalid = |message: String| AppError::InvalidProfileFile {
+            path: path.display().to_string(),
+            message,
+        };
+        let content = fs::read_to_string(&path).map_err(AppError::GlobalConfigError)?;
+        let value = parse_config_value(ConfigFormat::from_path(&path), &content)
+            .map_err(|e| invalid(e.to_string()))?;
+        let profile = serde_json::from_value(value).map_err(|e| invalid(e.to_string()))?;
+        if profiles.insert(name.clone(), (path, profile)).is_some() {
+            return Err(AppError::DuplicateProfileFile(name));
+        }
+    }
+    Ok(profiles)
+}
+
+/// Profile files take precedence over profiles left in the config file.
+pub fn read_external_profiles(
+    config_path: &Path,
+    app_config: &mut AppConfig,
+) -> Result<(), AppError> {
+    let dir = profiles_dir_for(config_path);
+    if dir.is_dir() {
+        read_user_profiles(config_path, &dir, app_config)?;
+    }
+    read_system_profiles(app_config)
+}
+
+fn read_user_profiles(
+    config_path: &Path,
+    dir: &Path,
+    app_config: &mut AppConfig,
+) -> Result<(), AppError> {
+    for (name, (_, profile)) in read_profile_files(dir)? {
+        app_config.profiles.insert(name, profile);
+    }
+    let state_path = state_path_for(config_path);
+    if state_path.exists() {
+        let content = fs::read_to_string(&state_path).map_err(AppError::GlobalConfigError)?;
+        let state: AppState =
+            serde_json::from_str(&content).map_err(|e| AppError::InvalidProfileFile {
+                path: state_path.display().to_string(),
+                message: e.to_string(),
+            })?;
+        app_config.current_profile = state.current_profile;
+        app_config.previous_profile = state.previous_profile;
+    }
+    Ok(())
+}
+
+fn read_system_profiles(app_config: &mut AppConfig) -> Result<(), AppError> {
+    let dir = get_system_profiles_dir();
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    for (name, (_, profile)) in read_profile_files(&dir)? {
+        if app_config.profiles.contains_key(&name) {
+            app_config.shadowed_profiles.insert(name);
+        } else {
+            app_config.profiles.insert(name.clone(), profile);
+            app_config.system_profiles.insert(name);
+        }
+    }
+    Ok(())
+}
+
+pub fn ensure_user_profile(app_config: &AppConfig, name: &str) -> Result<(), AppError> {
+    if app_config.system_profiles.contains(name) {
+        return Err(AppError::SystemProfileReadOnly(name.to_string()));
+    }
+    Ok(())
+}
+
+fn user_app_config(app_config: &AppConfig) -> AppConfig {
+    let mut user_config = app_config.clone();
+    user_config
+        .profiles
+        .retain(|name, _| !app_config.system_profiles.contains(name));
+    user_config
+}
+
+/// Symlinked files are written through, so dotfiles repositories stay linked.
+fn write_profile_files(
+    dir: &Path,
+    profiles: &HashMap<String, GitUserConfig>,
+    format: ConfigFormat,
+) -> Result<(), AppError> {
+    let existing = read_profile_files(dir)?;
+    for (name, profile) in profiles {
+        let path = match existing.get(name) {
+            Some((_, stored)) if stored == profile => continue,
+            Some((path, _)) => path.clone(),
+            None => dir.join(profile_file_name(name, format)?),
+        };
+        let content = serialize_for_file(&path, profile)?;
+        write_private_file(&path, &content)?;
+    }
+    for (name, (path, _)) in &existing {
+        if !profiles.contains_key(name) {
+            remove_managed_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+fn stored_app_config(config_path: &Path, app_config: &AppConfig) -> AppConfig {
+    let user_config = user_app_config(app_config);
+    if !profiles_dir_for(config_path).is_dir() {
+        return user_config;
+    }
+    AppConfig {
+        profiles: HashMap::new(),
+        current_profile: None,
+        previous_profile: None,
+        ..user_config
+    }
+}
+
+fn write_app_config(path: &Path, app_config: &AppConfig) -> Result<(), AppError> {
+    let format = ConfigFormat::from_path(path);
+    let dir = profiles_dir_for(path);
+    if dir.is_dir() {
+        write_profile_files(&dir, &user_app_config(app_config).profiles, format)?;
+        let state = AppState {
+            current_profile: app_config.current_profile.clone(),
+            previous_profile: app_config.previous_profile.clone(),
+        };
+        write_private_file(
+            &state_path_for(path),
+            &serde_json::to_string_pretty(&state)?,
+        )?;
+    }
+    let content = serialize_for_file(path, &stored_app_config(path, app_config))?;
+    write_private_file(path, &content)
+}
+
+fn remove_managed_file(path: &Path) -> Result<(), AppError> {
+    if is_dry_run() {
+        record_planned_change(PlannedChange::File {
+            path: path.to_path_buf(),
+            old: fs::read_to_string(path).ok(),
+            new: None,
+        });
+        return Ok(());
+    }
+    fs::remove_file(path).map_err(AppError::GlobalConfigError)
+}
+
+fn write_private_file(path: &Path, content: &str) -> Result<(), AppError> {
+    if is_dry_run() {
+        record_planned_change(PlannedChange::File {
+            path: path.to_path_buf(),
+            old: fs::read_to_string(path).ok(),
+            new: Some(content.to_string()),
+        });
+        return Ok(());
+    }
+    // Renaming over a symlink would replace it, so write to its target instead.
+    let path = &fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let temp_path = with_suffix(path, &format!(".tmp.{}", std::process::id()));
+    let write = || -> std::io::Result<()> {
+        let mut file = platform::create_private_file(&temp_path)?;
+        file.write_all(content.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temp_path, path)
+    };
+    write().map_err(|e| {
+        fs::remove_file(&temp_path).ok();
+        AppError::GlobalConfigError(e)
+    })
+}
+
+fn lock_app_config(path: &Path) -> Result<Option<fs::File>, AppError> {
+    if is_dry_run() {
+        return Ok(None);
+    }
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent).map_err(AppError::GlobalConfigError)?;
     }
-    let content = serde_json::to_string_pretty(config).map_err(AppError::SerializationError)?;
-    fs::write(path, content).map_err(AppError::GlobalConfigError)
+    let lock = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(lock_path_for(path))
+        .map_err(AppError::GlobalConfigError)?;
+    FileExt::lock_exclusive(&lock).map_err(AppError::GlobalConfigError)?;
+    Ok(Some(lock))
+}
+
+pub fn load_app_config() -> Result<AppConfig, AppError> {
+    let path = get_app_config_path()?;
+    let (app_config, backup) = read_app_config(&path)?;
+    if backup.is_none() {
+        return Ok(app_config);
+    }
+    update_app_config(|app_config| Ok(app_config.clone()))
+}
+
+/// `update` must not load the config file itself.
+pub fn update_app_config<T>(
+    update: impl FnOnce(&mut AppConfig) -> Result<T, AppError>,
+) -> Result<T, AppError> {
+    let path = get_app_config_path()?;
+    let _lock = lock_app_config(&path)?;
+    let (mut app_config, backup) = read_app_config(&path)?;
+    let result = update(&mut app_config)?;
+    if let Some(backup) = backup {
+        write_backup(&backup)?;
+    }
+    write_app_config(&path, &app_config)?;
+    Ok(result)
+}
+
+pub fn convert_app_config(to: ConfigFormat) -> Result<(PathBuf, PathBuf), AppError> {
+    let path = get_app_config_path()?;
+    let _lock = lock_app_config(&path)?;
+    if ConfigFormat::from_path(&path) == to {
+        return Err(AppError::ConfigAlreadyInFormat(to.as_str()));
+    }
+    let target = path.with_extension(to.extension());
+    if target.exists() {
+        return Err(AppError::ConfigFileExists(target.display().to_string()));
+    }
+
+    let (app_config, _) = read_app_config(&path)?;
+    let app_config = stored_app_config(&path, &app_config);
+    let content = serialize_config(to, &app_config)?;
+    let round_trip: AppConfig = serde_json::from_value(parse_config_value(to, &content)?)?;
+    if round_trip != app_config {
+        return Err(AppError::ConfigFormatError {
+            format: to.as_str(),
+            message: "the converted file does not match the original".to_string(),
+        });
+    }
+    write_private_file(&target, &content)?;
+
+    let backup = with_suffix(&path, ".bak");
+    if path.exists() {
+        fs::rename(&path, &backup).map_err(AppError::GlobalConfigError)?;
+    }
+    Ok((target, backup))
+}
+
+pub fn split_profiles() -> Result<PathBuf, AppError> {
+    let path = get_app_config_path()?;
+    let _lock = lock_app_config(&path)?;
+    let dir = profiles_dir_for(&path);
+    if dir.exists() {
+        return Err(AppError::ProfilesAlreadySplit(dir.display().to_string()));
+    }
+    let (app_config, backup) = read_app_config(&path)?;
+    if let Some(backup) = backup {
+        write_backup(&backup)?;
+    }
+    let format = ConfigFormat::from_path(&path);
+    for name in app_config.profiles.keys() {
+        profile_file_name(name, format)?;
+    }
+    fs::create_dir(&dir).map_err(AppError::GlobalConfigError)?;
+    write_app_config(&path, &app_config)?;
+    Ok(dir)
+}
+
+pub fn join_profiles() -> Result<PathBuf, AppError> {
+    let path = get_app_config_path()?;
+    let _lock = lock_app_config(&path)?;
+    let dir = profiles_dir_for(&path);
+    if !dir.is_dir() {
+        return Err(AppError::ProfilesNotSplit);
+    }
+    let (app_config, backup) = read_app_config(&path)?;
+    if let Some(backup) = backup {
+        write_backup(&backup)?;
+    }
+    let content = serialize_for_file(&path, &user_app_config(&app_config))?;
+    write_private_file(&path, &content)?;
+    for (file, _) in read_profile_files(&dir)?.into_values() {
+        remove_managed_file(&file)?;
+    }
+    let state_path = state_path_for(&path);
+    if state_path.exists() {
+        remove_managed_file(&state_path)?;
+    }
+    fs::remove_dir(&dir).ok();
+    Ok(path)
+}
+
+fn read_history(path: &Path) -> Result<Vec<HistoryEntry>, AppError> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(path).map_err(AppError::GlobalConfigError)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+pub fn load_history() -> Result<Vec<HistoryEntry>, AppError> {
+    read_history(&get_history_path()?)
+}
+
+pub fn record_history(command: &str) -> Result<(), AppError> {
+    if is_dry_run() {
+        return Ok(());
+    }
+    let app_config = load_app_config()?;
+    let entry = HistoryEntry {
+        timestamp: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default(),
+        command: command.to_string(),
+        profile: app_config.current_profile.clone(),
+        config: get_git_config(&ConfigScope::Global)?,
+        values: raw_git_values(&app_config),
+    };
+    let path = get_history_path()?;
+    let _lock = lock_app_config(&get_app_config_path()?)?;
+    let mut history = read_history(&path)?;
+    history.insert(0, entry);
+    history.truncate(HISTORY_LIMIT);
+    write_private_file(&path, &serde_json::to_string_pretty(&history)?)
+}
+
+fn raw_git_values(app_config: &AppConfig) -> BTreeMap<String, Option<String>> {
+    let scope = ConfigScope::Global;
+    ["user.name", "user.email", "user.signingkey", "gpg.format"]
+        .into_iter()
+        .map(str::to_string)
+        .chain(SigningFormat::ALL.into_iter().map(signing_program_key))
+        .chain(
+            SIGNING_POLICY_KEYS
+                .iter()
+                .map(|(key, _, _)| key.to_string()),
+        )
+        .chain([SSH_COMMAND_KEY, PREVIOUS_SSH_COMMAND_KEY].map(str::to_string))
+        .chain(managed_extra_keys(app_config))
+        .map(|key| {
+            let value = git_config(&scope, &[&key]).ok();
+            (key, value)
+        })
+        .collect()
+}
+
+pub fn restore_git_config(values: &BTreeMap<String, Option<String>>) -> Result<(), AppError> {
+    let scope = ConfigScope::Global;
+    for (key, value) in values {
+        match value {
+            Some(value) => {
+                git_config(&scope, &[key, value])?;
+            }
+            None => {
+                git_config(&scope, &["--unset-all", key]).ok();
+            }
+        }
+    }
+    keep_includes_last(&load_app_config()?)
+}
+
+fn planned_git_change(scope: &ConfigScope, args: &[&str]) -> Option<PlannedChange> {
+    let read = |key: &str| git_config(scope, &[key]).ok();
+    match args {
+        ["--unset-all", key] => Some(PlannedChange::GitConfig {
+            scope: scope.describe(),
+            key: key.to_string(),
+            old: read(key),
+            new: None,
+        }),
+        ["--remove-section", section] => Some(PlannedChange::RemoveSection {
+            scope: scope.describe(),
+            section: section.to_string(),
+        }),
+        [key, value] if !key.starts_with('-') => Some(PlannedChange::GitConfig {
+            scope: scope.describe(),
+            key: key.to_string(),
+            old: read(key),
+            new: Some(value.to_string()),
+        }),
+        _ => None,
+    }
+}
+
+fn git_config(scope: &ConfigScope, args: &[&str]) -> Result<String, AppError> {
+    if is_dry_run() {
+        if let Some(change) = planned_git_change(scope, args) {
+            record_planned_change(change);
+            return Ok(String::new());
+        }
+    }
+    let scope_args = scope.args();
+    let mut full_args = vec!["config"];
+    full_args.extend(scope_args.iter().map(String::as_str));
+    full_args.extend_from_slice(args);
+    platform::run_command("git", &full_args)
+}
+
+fn ensure_scope_available(scope: &ConfigScope) -> Result<(), AppError> {
+    if *scope == ConfigScope::Local && !platform::is_inside_git_repository() {
+        return Err(AppError::NotInGitRepository);
+    }
+    Ok(())
 }
 
-pub fn get_git_config() -> Result<GitUserConfig, AppError> {
-    let name = platform::run_command("git", &["config", "--global", "user.name"]).ok();
-    let email = platform::run_command("git", &["config", "--global", "user.email"]).ok();
-    let signing_key = platform::run_command("git", &["config", "--global", "user.signingkey"]).ok();
+const RESERVED_KEYS: [&str; 6] = [
+    "user.name",
+    "user.email",
+    "user.signingkey",
+    "commit.gpgsign",
+    "tag.gpgsign",
+    "push.gpgsign",
+];
+
+fn managed_extra_keys(app_config: &AppConfig) -> BTreeSet<String> {
+    app_config
+        .profiles
+        .values()
+        .flat_map(|profile| profile.extra_config.keys().cloned())
+        .collect()
+}
+
+const SSH_COMMAND_KEY: &str = "core.sshCommand";
+const PREVIOUS_SSH_COMMAND_KEY: &str = "gitup.previousSshCommand";
+
+fn signing_program_key(format: SigningFormat) -> String {
+    format!("gpg.{}.program", format.as_str())
+}
+
+/// `push.gpgSign=true` fails against servers without signed pushes.
+const SIGNING_POLICY_KEYS: [(&str, &str, &str); 3] = [
+    ("commit.gpgsign", "true", "false"),
+    ("tag.gpgSign", "true", "false"),
+    ("push.gpgSign", "if-asked", "false"),
+];
+
+pub fn validate_signing_key(config: &GitUserConfig) -> Result<(), AppError> {
+    if config.effective_signing_policy() == SigningPolicy::Always
+        && config.effective_signing_format().is_none()
+    {
+        return Err(AppError::SigningKeyRequired);
+    }
+    match (&config.signing_key, config.effective_signing_format()) {
+        (Some(key), Some(format)) => platform::check_signing_key(key, format).map_err(|reason| {
+            AppError::SigningKeyNotFound {
+                key: key.clone(),
+                reason,
+            }
+        }),
+        _ => Ok(()),
+    }
+}
+
+fn ssh_command_for_key(path: &str) -> String {
+    format!(
+        "ssh -i '{}' -o IdentitiesOnly=yes",
+        path.replace('\'', "'\\''")
+    )
+}
+
+/// `None` if the command was not written by `ssh_command_for_key`.
+pub fn ssh_key_from_command(command: &str) -> Option<String> {
+    let quoted = command
+        .strip_prefix("ssh -i '")?
+        .strip_suffix("' -o IdentitiesOnly=yes")?;
+    Some(quoted.replace("'\\''", "'"))
+}
+
+/// `None` when `commit.gpgSign` is unset. Git normalizes its boolean spellings.
+pub fn read_signing_policy(scope: &ConfigScope) -> Option<SigningPolicy> {
+    git_config(scope, &["--type=bool", "commit.gpgsign"])
+        .ok()
+        .map(|value| SigningPolicy::from_gpgsign(&value))
+}
+
+pub fn get_git_config(scope: &ConfigScope) -> Result<GitUserConfig, AppError> {
+    ensure_scope_available(scope)?;
+    let app_config = load_app_config()?;
+    let name = git_config(scope, &["user.name"]).ok();
+    let email = git_config(scope, &["user.email"]).ok();
+    let signing_key = git_config(scope, &["user.signingkey"]).ok();
+    let signing_format = signing_key.as_ref().and_then(|_| {
+        git_config(scope, &["gpg.format"])
+            .ok()
+            .and_then(|format| SigningFormat::parse(&format))
+    });
+    let signing_program = signing_key.as_deref().and_then(|key| {
+        let format = signing_format.unwrap_or_else(|| SigningFormat::detect(key));
+        git_config(scope, &[&signing_program_key(format)]).ok()
+    });
+    let signing_policy = read_signing_policy(scope);
+    let ssh_key = git_config(scope, &[SSH_COMMAND_KEY])
+        .ok()
+        .and_then(|command| ssh_key_from_command(&command));
+    let extra_config = managed_extra_keys(&app_config)
+        .into_iter()
+        .filter(|key| !(ssh_key.is_some() && key.eq_ignore_ascii_case(SSH_COMMAND_KEY)))
+        .filter_map(|key| git_config(scope, &[&key]).ok().map(|value| (key, value)))
+        .collect();
     Ok(GitUserConfig {
+        extends: None,
         name,
         email,
         signing_key,
+        signing_format,
+        signing_program,
+        signing_policy,
+        ssh_key,
+        extra_config,
     })
 }
 
-pub fn set_git_config(config: &GitUserConfig) -> Result<(), AppError> {
+fn config_fields(config: &GitUserConfig) -> Vec<(String, Option<String>)> {
+    let mut fields: Vec<(String, Option<String>)> = [
+        ("name", config.name.clone()),
+        ("email", config.email.clone()),
+        ("signing key", config.signing_key.clone()),
+        (
+            "signing format",
+            config
+                .effective_signing_format()
+                .map(|format| format.as_str().to_string()),
+        ),
+        ("signing program", config.signing_program.clone()),
+        (
+            "signing policy",
+            Some(config.effective_signing_policy().as_str().to_string()),
+        ),
+        ("SSH key", config.ssh_key.clone()),
+    ]
+    .into_iter()
+    .map(|(field, value)| (field.to_string(), value))
+    .collect();
+    fields.extend(
+        config
+            .extra_config
+            .iter()
+            .map(|(key, value)| (key.clone(), Some(value.clone()))),
+    );
+    fields
+}
+
+/// An `untouched` signing policy is not compared, since applying leaves it alone.
+pub fn profile_drift(profile: &GitUserConfig, live: &GitUserConfig) -> Vec<FieldDiff> {
+    let live_fields: HashMap<String, Option<String>> = config_fields(live).into_iter().collect();
+    config_fields(profile)
+        .into_iter()
+        .filter(|(field, _)| {
+            field != "signing policy"
+                || profile.effective_signing_policy() != SigningPolicy::Untouched
+        })
+        .map(|(field, expected)| {
+            let actual = live_fields.get(&field).cloned().flatten();
+            FieldDiff {
+                field,
+                expected,
+                actual,
+            }
+        })
+        .filter(|diff| diff.expected != diff.actual)
+        .collect()
+}
+
+pub fn compare_configs(left: &GitUserConfig, right: &GitUserConfig) -> Vec<FieldComparison> {
+    let left_fields = config_fields(left);
+    let right_fields = config_fields(right);
+    let mut comparison: Vec<FieldComparison> = Vec::new();
+    for (field, _) in left_fields.iter().chain(&right_fields) {
+        if comparison.iter().any(|row| row.field == *field) {
+            continue;
+        }
+        let value = |fields: &[(String, Option<String>)]| {
+            fields
+                .iter()
+                .find(|(candidate, _)| candidate == field)
+                .and_then(|(_, value)| value.clone())
+        };
+        let (left, right) = (value(&left_fields), value(&right_fields));
+        if left.is_some() || right.is_some() {
+            comparison.push(FieldComparison {
+                field: field.clone(),
+                differs: left != right,
+                left,
+                right,
+            });
+        }
+    }
+    comparison
+}
+
+pub fn profile_status(app_config: &AppConfig) -> Result<ProfileStatus, AppError> {
+    let Some(name) = app_config
+        .current_profile
+        .as_ref()
+        .filter(|name| app_config.profiles.contains_key(*name))
+    else {
+        return Ok(ProfileStatus::Unmatched);
+    };
+    let profile = resolve_profile(app_config, name)?;
+    let changes = profile_drift(&profile, &get_git_config(&ConfigScope::Global)?);
+    if changes.is_empty() {
+        Ok(ProfileStatus::Active {
+            profile: name.clone(),
+        })
+    } else {
+        Ok(ProfileStatus::Modified {
+            profile: name.clone(),
+            changes,
+        })
+    }
+}
+
+pub fn detect_profiles(app_config: &AppConfig) -> Result<Vec<String>, AppError> {
+    let live = get_git_config(&ConfigScope::Global)?;
+    let mut matches: Vec<String> = app_config
+        .profiles
+        .keys()
+        .filter(|name| {
+            resolve_profile(app_config, name)
+                .is_ok_and(|profile| profile_drift(&profile, &live).is_empty())
+        })
+        .cloned()
+        .collect();
+    matches.sort();
+    Ok(matches)
+}
+
+pub fn get_effective_git_config() -> Result<(GitUserConfig, HashMap<String, String>), AppError> {
+    let app_config = load_app_config()?;
+    let mut origins = HashMap::new();
+    let mut read = |key: &str| -> Option<String> {
+        let output =
+            platform::run_command("git", &["config", "--show-scope", "--get", key]).ok()?;
+        let (scope, value) = output.split_once('\t')?;
+        origins.insert(key.to_string(), scope.to_string());
+        Some(value.to_string())
+    };
+    let mut config = GitUserConfig {
+        name: read("user.name"),
+        email: read("user.email"),
+        signing_key: read("user.signingkey"),
+        signing_policy: read("commit.gpgsign")
+            .and(platform::run_command("git", &["config", "--type=bool", "commit.gpgsign"]).ok())
+            .map(|value| SigningPolicy::from_gpgsign(&value)),
+        ssh_key: read(SSH_COMMAND_KEY).and_then(|command| ssh_key_from_command(&command)),
+        ..Default::default()
+    };
+    if let Some(key) = config.signing_key.clone() {
+        config.signing_format = read("gpg.format").and_then(|format| SigningFormat::parse(&format));
+        let format = config
+            .signing_format
+            .unwrap_or_else(|| SigningFormat::detect(&key));
+        config.signing_program = read(&signing_program_key(format));
+    }
+    for key in managed_extra_keys(&app_config) {
+        if config.ssh_key.is_some() && key.eq_ignore_ascii_case(SSH_COMMAND_KEY) {
+            continue;
+        }
+        if let Some(value) = read(&key) {
+            config.extra_config.insert(key, value);
+        }
+    }
+    Ok((config, origins))
+}
+
+pub fn snapshot_git_config(name: &str, keys: &[String]) -> Result<GitUserConfig, AppError> {
+    if let Some(key) = keys
+        .iter()
+        .find(|key| RESERVED_KEYS.contains(&key.to_lowercase().as_str()))
+    {
+        return Err(AppError::ReservedConfigKey(key.clone()));
+    }
+
+    let scope = ConfigScope::Global;
+    let app_config = load_app_config()?;
+    let mut config = get_git_config(&scope)?;
+    config.extra_config.clear();
+    for key in keys {
+        let value =
+            git_config(&scope, &[key]).map_err(|_| AppError::ConfigKeyNotSet(key.clone()))?;
+        config.extra_config.insert(key.clone(), value);
+    }
+    if let Some(existing) = app_config.profiles.get(name) {
+        for key in existing.extra_config.keys() {
+            if config.extra_config.contains_key(key) {
+                continue;
+            }
+            if let Ok(value) = git_config(&scope, &[key]) {
+                config.extra_config.insert(key.clone(), value);
+            }
+        }
+    }
+    Ok(config)
+}
+
+pub fn get_local_profile() -> Option<String> {
+    git_config(&ConfigScope::Local, &["gitup.profile"]).ok()
+}
+
+pub fn pin_local_profile(name: &str) -> Result<(), AppError> {
+    ensure_scope_available(&ConfigScope::Local)?;
+    git_config(&ConfigScope::Local, &["gitup.profile", name]).map(|_| ())
+}
+
+pub fn profile_entries(config: &GitUserConfig) -> Vec<(String, String)> {
+    let mut entries = Vec::new();
     if let Some(name) = &config.name {
-        platform::run_command("git", &["config", "--global", "user.name", name])?;
+        entries.push(("user.name".to_string(), name.clone()));
     }
     if let Some(email) = &config.email {
-        platform::run_command("git", &["config", "--global", "user.email", email])?;
+        entries.push(("user.email".to_string(), email.clone()));
+    }
+    if let (Some(key), Some(format)) = (&config.signing_key, config.effective_signing_format()) {
+        entries.push(("user.signingkey".to_string(), key.clone()));
+        entries.push(("gpg.format".to_string(), format.as_str().to_string()));
+        if let Some(program) = config.signing_program.as_deref().filter(|p| !p.is_empty()) {
+            entries.push((signing_program_key(format), program.to_string()));
+        }
+    }
+    let policy = config.effective_signing_policy();
+    for (key, always, never) in SIGNING_POLICY_KEYS {
+        let value = match policy {
+            SigningPolicy::Always => always,
+            SigningPolicy::Never => never,
+            SigningPolicy::Untouched => break,
+        };
+        entries.push((key.to_string(), value.to_string()));
     }
+    for (key, value) in &config.extra_config {
+        entries.push((key.clone(), value.clone()));
+    }
+    if let Some(path) = config.ssh_key.as_deref().filter(|path| !path.is_empty()) {
+        entries.push((SSH_COMMAND_KEY.to_string(), ssh_command_for_key(path)));
+    }
+    entries
+}
+
+fn write_user_config(
+    scope: &ConfigScope,
+    config: &GitUserConfig,
+    managed_keys: &BTreeSet<String>,
+) -> Result<(), AppError> {
+    let uses_ssh_key = config
+        .ssh_key
+        .as_deref()
+        .is_some_and(|path| !path.is_empty());
+    let current_ssh_command = git_config(scope, &[SSH_COMMAND_KEY]).ok();
+    let is_generated = current_ssh_command
+        .as_deref()
+        .is_some_and(|command| ssh_key_from_command(command).is_some());
 
-    match &config.signing_key {
-        Some(key) if !key.is_empty() => {
-            platform::run_command("git", &["config", "--global", "user.signingkey", key])?;
-            platform::run_command("git", &["config", "--global", "commit.gpgsign", "true"])?;
+    if uses_ssh_key && !is_generated {
+        if let Some(command) = &current_ssh_command {
+            git_config(scope, &[PREVIOUS_SSH_COMMAND_KEY, command])?;
         }
-        _ => {
-            platform::run_command(
-                "git",
-                &["config", "--global", "--unset-all", "user.signingkey"],
-            )
-            .ok();
-            platform::run_command("git", &["config", "--global", "commit.gpgsign", "false"])?;
+    }
+
+    let entries = profile_entries(config);
+    for (key, value) in &entries {
+        git_config(scope, &[key, value])?;
+    }
+    if config.signing_key.as_deref().unwrap_or("").is_empty() {
+        git_config(scope, &["--unset-all", "user.signingkey"]).ok();
+        git_config(scope, &["--unset-all", "gpg.format"]).ok();
+    }
+    if config.effective_signing_format().is_none()
+        && config.effective_signing_policy() == SigningPolicy::Untouched
+    {
+        // Signing without a key fails, so it is never left on for such a profile.
+        for (key, _, never) in SIGNING_POLICY_KEYS {
+            let is_on = match git_config(scope, &["--type=bool", key]) {
+                Ok(value) => value == "true",
+                Err(_) => git_config(scope, &[key]).is_ok(),
+            };
+            if is_on {
+                git_config(scope, &[key, never])?;
+            }
+        }
+    }
+    let written: BTreeSet<String> = entries.iter().map(|(key, _)| key.to_lowercase()).collect();
+    for format in SigningFormat::ALL {
+        let key = signing_program_key(format);
+        if !written.contains(&key.to_lowercase()) {
+            git_config(scope, &["--unset-all", &key]).ok();
+        }
+    }
+    for key in managed_keys {
+        if uses_ssh_key && key.eq_ignore_ascii_case(SSH_COMMAND_KEY) {
+            continue;
+        }
+        if !config.extra_config.contains_key(key) {
+            git_config(scope, &["--unset-all", key]).ok();
         }
     }
 
+    let defines_ssh_command = config
+        .extra_config
+        .keys()
+        .any(|key| key.eq_ignore_ascii_case(SSH_COMMAND_KEY));
+    if !uses_ssh_key && !defines_ssh_command && is_generated {
+        if let Ok(previous) = git_config(scope, &[PREVIOUS_SSH_COMMAND_KEY]) {
+            git_config(scope, &[SSH_COMMAND_KEY, &previous])?;
+        } else {
+            git_config(scope, &["--unset-all", SSH_COMMAND_KEY]).ok();
+        }
+        git_config(scope, &["--unset-all", PREVIOUS_SSH_COMMAND_KEY]).ok();
+    }
     Ok(())
 }
 
-pub fn save_profile(name: &str, config: &GitUserConfig) -> Result<(), AppError> {
-    let mut app_config = load_app_config()?;
-    if app_config.profiles.is_empty() {
-        app_config.profiles = HashMap::new();
+pub fn set_git_config(config: &GitUserConfig, scope: &ConfigScope) -> Result<(), AppError> {
+    ensure_scope_available(scope)?;
+    let app_config = load_app_config()?;
+    write_user_config(scope, config, &managed_extra_keys(&app_config))?;
+    if *scope == ConfigScope::Global {
+        keep_includes_last(&app_config)?;
     }
+    Ok(())
+}
+
+pub fn save_profile(name: &str, config: &GitUserConfig) -> Result<(), AppError> {
+    update_app_config(|app_config| store_profile(app_config, name, config))
+}
+
+pub fn store_profile(
+    app_config: &mut AppConfig,
+    name: &str,
+    config: &GitUserConfig,
+) -> Result<(), AppError> {
     app_config.profiles.insert(name.to_string(), config.clone());
-    save_app_config(&app_config)
+    if app_config.system_profiles.remove(name) {
+        app_config.shadowed_profiles.insert(name.to_string());
+    }
+    resolve_profile(app_config, name)?;
+    let affected: Vec<String> = app_config
+        .profiles
+        .keys()
+        .filter(|profile| profile_depends_on(app_config, profile, name))
+        .cloned()
+        .collect();
+    for profile in affected {
+        sync_profile_include(app_config, &profile)?;
+    }
+    Ok(())
+}
+
+pub fn profile_depends_on(app_config: &AppConfig, profile: &str, name: &str) -> bool {
+    extends_chain(app_config, profile).is_ok_and(|chain| chain.contains(&name))
+}
+
+pub fn rename_profile(old: &str, new: &str) -> Result<(), AppError> {
+    update_app_config(|app_config| {
+        if app_config.profiles.contains_key(new) {
+            return Err(AppError::ProfileAlreadyExists(new.to_string()));
+        }
+        ensure_user_profile(app_config, old)?;
+        let profile = app_config
+            .profiles
+            .remove(old)
+            .ok_or_else(|| AppError::ProfileNotFound(old.to_string()))?;
+        app_config.profiles.insert(new.to_string(), profile);
+
+        for profile in app_config.profiles.values_mut() {
+            if profile.extends.as_deref() == Some(old) {
+                profile.extends = Some(new.to_string());
+            }
+        }
+        for active in [
+            &mut app_config.current_profile,
+            &mut app_config.previous_profile,
+        ] {
+            if active.as_deref() == Some(old) {
+                *active = Some(new.to_string());
+            }
+        }
+
+        let include_path = get_profile_include_path(new)?.display().to_string();
+        for (dir, profile) in app_config.bindings.iter_mut() {
+            if profile == old {
+                *profile = new.to_string();
+                git_config(
+                    &ConfigScope::Global,
+                    &[&include_key(&gitdir_include_section(dir)), &include_path],
+                )?;
+            }
+        }
+        for (pattern, profile) in app_config.rules.iter_mut() {
+            if profile == old {
+                *profile = new.to_string();
+                git_config(
+                    &ConfigScope::Global,
+                    &[
+                        &include_key(&remote_include_section(pattern)),
+                        &include_path,
+                    ],
+                )?;
+            }
+        }
+
+        sync_profile_include(app_config, old)?;
+        sync_profile_include(app_config, new)?;
+        Ok(())
+    })
+}
+
+pub fn copy_profile(source: &str, destination: &str) -> Result<(), AppError> {
+    update_app_config(|app_config| {
+        if app_config.profiles.contains_key(destination) {
+            return Err(AppError::ProfileAlreadyExists(destination.to_string()));
+        }
+        let profile = app_config
+            .profiles
+            .get(source)
+            .ok_or_else(|| AppError::ProfileNotFound(source.to_string()))?
+            .clone();
+        app_config.profiles.insert(destination.to_string(), profile);
+        Ok(())
+    })
+}
+
+fn extends_chain<'a>(app_config: &'a AppConfig, name: &'a str) -> Result<Vec<&'a str>, AppError> {
+    let mut chain = Vec::new();
+    let mut current = name;
+    loop {
+        if chain.contains(&current) {
+            chain.push(current);
+            return Err(AppError::ProfileCycle(chain.join(" -> ")));
+        }
+        let profile = app_config
+            .profiles
+            .get(current)
+            .ok_or_else(|| AppError::ProfileNotFound(current.to_string()))?;
+        chain.push(current);
+        match profile.extends.as_deref() {
+            Some(parent) => current = parent,
+            None => return Ok(chain),
+        }
+    }
+}
+
+fn inherit<T: Clone>(
+    target: &mut Option<T>,
+    value: &Option<T>,
+    origins: &mut HashMap<String, String>,
+    key: &str,
+    profile: &str,
+) {
+    if value.is_some() {
+        *target = value.clone();
+        origins.insert(key.to_string(), profile.to_string());
+    }
+}
+
+/// The signing key, format and program are inherited together.
+pub fn resolve_profile_with_origins(
+    app_config: &AppConfig,
+    name: &str,
+) -> Result<(GitUserConfig, HashMap<String, String>), AppError> {
+    let chain = extends_chain(app_config, name)?;
+    let mut resolved = GitUserConfig::default();
+    let mut origins = HashMap::new();
+    for profile_name in chain.into_iter().rev() {
+        let profile = &app_config.profiles[profile_name];
+        let origins = &mut origins;
+        inherit(
+            &mut resolved.name,
+            &profile.name,
+            origins,
+            "user.name",
+            profile_name,
+        );
+        inherit(
+            &mut resolved.email,
+            &profile.email,
+            origins,
+            "user.email",
+            profile_name,
+        );
+        if profile.signing_key.is_some() {
+            resolved.signing_format = profile.signing_format;
+            resolved.signing_program = profile.signing_program.clone();
+        }
+        inherit(
+            &mut resolved.signing_key,
+            &profile.signing_key,
+            origins,
+            "user.signingkey",
+            profile_name,
+        );
+        inherit(
+            &mut resolved.signing_policy,
+            &profile.signing_policy,
+            origins,
+            "commit.gpgsign",
+            profile_name,
+        );
+        inherit(
+            &mut resolved.ssh_key,
+            &profile.ssh_key,
+            origins,
+            SSH_COMMAND_KEY,
+            profile_name,
+        );
+        for (key, value) in &profile.extra_config {
+            resolved.extra_config.insert(key.clone(), value.clone());
+            origins.insert(key.clone(), profile_name.to_string());
+        }
+    }
+    Ok((resolved, origins))
+}
+
+pub fn resolve_profile(app_config: &AppConfig, name: &str) -> Result<GitUserConfig, AppError> {
+    resolve_profile_with_origins(app_config, name).map(|(profile, _)| profile)
+}
+
+pub fn strip_inherited(
+    app_config: &AppConfig,
+    base: &str,
+    config: &GitUserConfig,
+) -> Result<GitUserConfig, AppError> {
+    let inherited = resolve_profile(app_config, base)?;
+    let mut own = config.clone();
+    own.extends = Some(base.to_string());
+    if own.name == inherited.name {
+        own.name = None;
+    }
+    if own.email == inherited.email {
+        own.email = None;
+    }
+    if own.effective_signing_policy() == inherited.effective_signing_policy() {
+        own.signing_policy = None;
+    }
+    if own.signing_key == inherited.signing_key
+        && own.effective_signing_format() == inherited.effective_signing_format()
+        && own.signing_program == inherited.signing_program
+    {
+        own.signing_key = None;
+        own.signing_format = None;
+        own.signing_program = None;
+    }
+    if own.ssh_key == inherited.ssh_key {
+        own.ssh_key = None;
+    }
+    own.extra_config
+        .retain(|key, value| inherited.extra_config.get(key) != Some(value));
+    Ok(own)
 }
 
-pub fn use_profile(name: &str) -> Result<GitUserConfig, AppError> {
-    let mut app_config = load_app_config()?;
-    let git_config = app_config
+pub fn profile_children(app_config: &AppConfig, name: &str) -> Vec<String> {
+    let mut children: Vec<String> = app_config
         .profiles
-        .get(name)
-        .ok_or_else(|| AppError::ProfileNotFound(name.to_string()))?
-        .clone();
-    set_git_config(&git_config)?;
-    app_config.current_profile = Some(name.to_string());
-    save_app_config(&app_config)?;
-    Ok(git_config)
+        .iter()
+        .filter(|(_, profile)| profile.extends.as_deref() == Some(name))
+        .map(|(child, _)| child.clone())
+        .collect();
+    children.sort();
+    children
+}
+
+pub fn use_profile(name: &str, scope: &ConfigScope) -> Result<GitUserConfig, AppError> {
+    let profile = resolve_profile(&load_app_config()?, name)?;
+    if *scope == ConfigScope::Global {
+        record_history(&format!("use {}", name))?;
+    }
+    set_git_config(&profile, scope)?;
+    if *scope == ConfigScope::Local {
+        pin_local_profile(name)?;
+    } else {
+        update_app_config(|app_config| {
+            app_config.switch_profile(Some(name.to_string()));
+            Ok(())
+        })?;
+    }
+    Ok(profile)
+}
+
+pub fn normalize_bind_dir(dir: &Path) -> Result<String, AppError> {
+    let expanded = platform::expand_home(dir);
+    let absolute = match fs::canonicalize(&expanded) {
+        Ok(path) => path,
+        Err(_) => std::path::absolute(&expanded)?,
+    };
+    let normalized = absolute.display().to_string().replace('\\', "/");
+    Ok(normalized.trim_end_matches('/').to_string())
+}
+
+fn gitdir_include_section(dir: &str) -> String {
+    format!("includeIf.gitdir:{}/", dir)
+}
+
+fn remote_include_section(pattern: &str) -> String {
+    format!("includeIf.hasconfig:remote.*.url:{}", pattern)
+}
+
+fn include_key(section: &str) -> String {
+    format!("{}.path", section)
+}
+
+enum IncludeCondition<'a> {
+    Gitdir(&'a str),
+    Remote(&'a str),
+}
+
+impl IncludeCondition<'_> {
+    fn section(&self) -> String {
+        match self {
+            IncludeCondition::Gitdir(dir) => gitdir_include_section(dir),
+            IncludeCondition::Remote(pattern) => remote_include_section(pattern),
+        }
+    }
+}
+
+/// In the order they are written. Git lets the last matching section win, so
+/// rules come before bindings and shorter patterns before longer ones.
+fn include_sections(app_config: &AppConfig) -> Vec<(IncludeCondition<'_>, &str)> {
+    let mut rules: Vec<(&String, &String)> = app_config.rules.iter().collect();
+    rules.sort_by_key(|(pattern, _)| (pattern.len(), *pattern));
+    let mut bindings: Vec<(&String, &String)> = app_config.bindings.iter().collect();
+    bindings.sort_by_key(|(dir, _)| (dir.len(), *dir));
+    rules
+        .into_iter()
+        .map(|(pattern, profile)| (IncludeCondition::Remote(pattern), profile.as_str()))
+        .chain(
+            bindings
+                .into_iter()
+                .map(|(dir, profile)| (IncludeCondition::Gitdir(dir), profile.as_str())),
+        )
+        .collect()
+}
+
+/// Anything written after gitup's includeIf sections would override every
+/// binding and rule, so the sections are moved back to the end.
+fn keep_includes_last(app_config: &AppConfig) -> Result<(), AppError> {
+    let sections: Vec<(String, &str)> = include_sections(app_config)
+        .into_iter()
+        .map(|(condition, profile)| (condition.section(), profile))
+        .collect();
+    if sections.is_empty() || is_dry_run() {
+        return Ok(());
+    }
+    let scope = ConfigScope::Global;
+    let names = git_config(&scope, &["--name-only", "--list"]).unwrap_or_default();
+    let names: Vec<&str> = names.lines().collect();
+    let in_place = names.len() >= sections.len()
+        && names[names.len() - sections.len()..]
+            .iter()
+            .zip(&sections)
+            .all(|(name, (section, _))| name.eq_ignore_ascii_case(&include_key(section)));
+    if in_place {
+        return Ok(());
+    }
+    for (section, _) in &sections {
+        git_config(&scope, &["--remove-section", section]).ok();
+    }
+    for (section, profile) in &sections {
+        let include_path = get_profile_include_path(profile)?;
+        git_config(
+            &scope,
+            &[&include_key(section), &include_path.display().to_string()],
+        )?;
+    }
+    Ok(())
+}
+
+fn sync_profile_include(app_config: &AppConfig, name: &str) -> Result<(), AppError> {
+    let path = get_profile_include_path(name)?;
+    let is_bound = app_config
+        .bindings
+        .values()
+        .chain(app_config.rules.values())
+        .any(|p| p == name);
+    match app_config.profiles.get(name) {
+        Some(_) if is_bound => {
+            let profile = resolve_profile(app_config, name)?;
+            if !is_dry_run() {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).map_err(AppError::GlobalConfigError)?;
+                }
+                if path.exists() {
+                    fs::remove_file(&path).map_err(AppError::GlobalConfigError)?;
+                }
+            }
+            write_user_config(&ConfigScope::File(path), &profile, &BTreeSet::new())
+        }
+        _ => {
+            if path.exists() {
+                remove_managed_file(&path)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+pub fn bind_directory(dir: &Path, profile: &str) -> Result<String, AppError> {
+    update_app_config(|app_config| {
+        if !app_config.profiles.contains_key(profile) {
+            return Err(AppError::ProfileNotFound(profile.to_string()));
+        }
+        let dir = normalize_bind_dir(dir)?;
+        let previous = app_config.bindings.insert(dir.clone(), profile.to_string());
+
+        sync_profile_include(app_config, profile)?;
+        let include_path = get_profile_include_path(profile)?;
+        git_config(
+            &ConfigScope::Global,
+            &[
+                &include_key(&gitdir_include_section(&dir)),
+                &include_path.display().to_string(),
+            ],
+        )?;
+        keep_includes_last(app_config)?;
+        if let Some(previous) = previous.filter(|p| p != profile) {
+            sync_profile_include(app_config, &previous)?;
+        }
+
+        Ok(dir)
+    })
+}
+
+pub fn unbind_directory(dir: &Path) -> Result<(String, String), AppError> {
+    update_app_config(|app_config| {
+        let dir = normalize_bind_dir(dir)?;
+        let profile = app_config
+            .bindings
+            .remove(&dir)
+            .ok_or_else(|| AppError::BindingNotFound(dir.clone()))?;
+
+        git_config(
+            &ConfigScope::Global,
+            &["--remove-section", &gitdir_include_section(&dir)],
+        )
+        .ok();
+        sync_profile_include(app_config, &profile)?;
+
+        Ok((dir, profile))
+    })
+}
+
+pub fn add_remote_rule(pattern: &str, profile: &str) -> Result<(), AppError> {
+    update_app_config(|app_config| {
+        if !app_config.profiles.contains_key(profile) {
+            return Err(AppError::ProfileNotFound(profile.to_string()));
+        }
+        let previous = app_config
+            .rules
+            .insert(pattern.to_string(), profile.to_string());
+
+        sync_profile_include(app_config, profile)?;
+        let include_path = get_profile_include_path(profile)?;
+        git_config(
+            &ConfigScope::Global,
+            &[
+                &include_key(&remote_include_section(pattern)),
+                &include_path.display().to_string(),
+            ],
+        )?;
+        keep_includes_last(app_config)?;
+        if let Some(previous) = previous.filter(|p| p != profile) {
+            sync_profile_include(app_config, &previous)?;
+        }
+
+        Ok(())
+    })
+}
+
+pub fn remove_remote_rule(pattern: &str) -> Result<String, AppError> {
+    update_app_config(|app_config| {
+        let profile = app_config
+            .rules
+            .remove(pattern)
+            .ok_or_else(|| AppError::RuleNotFound(pattern.to_string()))?;
+
+        git_config(
+            &ConfigScope::Global,
+            &["--remove-section", &remote_include_section(pattern)],
+        )
+        .ok();
+        sync_profile_include(app_config, &profile)?;
+
+        Ok(profile)
+    })
+}
+
+pub fn detach_profile(app_config: &mut AppConfig, name: &str) -> Result<(), AppError> {
+    let dirs: Vec<String> = app_config
+        .bindings
+        .iter()
+        .filter(|(_, profile)| profile.as_str() == name)
+        .map(|(dir, _)| dir.clone())
+        .collect();
+    for dir in dirs {
+        app_config.bindings.remove(&dir);
+        git_config(
+            &ConfigScope::Global,
+            &["--remove-section", &gitdir_include_section(&dir)],
+        )
+        .ok();
+    }
+
+    let patterns: Vec<String> = app_config
+        .rules
+        .iter()
+        .filter(|(_, profile)| profile.as_str() == name)
+        .map(|(pattern, _)| pattern.clone())
+        .collect();
+    for pattern in patterns {
+        app_config.rules.remove(&pattern);
+        git_config(
+            &ConfigScope::Global,
+            &["--remove-section", &remote_include_section(&pattern)],
+        )
+        .ok();
+    }
+
+    sync_profile_include(app_config, name)
+}
+
+/// Git wildmatch: `*` and `?` stop at `/`, `**` matches across it.
+fn glob_matches(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some(b'*') if pattern.get(1) == Some(&b'*') => {
+            let rest = &pattern[2..];
+            (0..=text.len()).any(|i| glob_matches(rest, &text[i..]))
+        }
+        Some(b'*') => {
+            let rest = &pattern[1..];
+            for i in 0..=text.len() {
+                if glob_matches(rest, &text[i..]) {
+                    return true;
+                }
+                if text.get(i) == Some(&b'/') {
+                    break;
+                }
+            }
+            false
+        }
+        Some(b'?') => {
+            matches!(text.first(), Some(c) if *c != b'/') && glob_matches(&pattern[1..], &text[1..])
+        }
+        Some(c) => text.first() == Some(c) && glob_matches(&pattern[1..], &text[1..]),
+    }
+}
+
+/// A profile pinned with `use --local` wins, then the last matching includeIf.
+pub fn resolve_profile_for_dir(app_config: &AppConfig, dir: &Path) -> Option<String> {
+    let dir_arg = dir.display().to_string();
+    let git_dir =
+        platform::run_command("git", &["-C", &dir_arg, "rev-parse", "--absolute-git-dir"]).ok();
+
+    if git_dir.is_some() {
+        let local_profile = platform::run_command(
+            "git",
+            &["-C", &dir_arg, "config", "--local", "gitup.profile"],
+        );
+        if let Ok(profile) = local_profile {
+            if app_config.profiles.contains_key(&profile) {
+                return Some(profile);
+            }
+        }
+    }
+
+    let location = match &git_dir {
+        Some(git_dir) => normalize_bind_dir(Path::new(git_dir)),
+        None => normalize_bind_dir(dir),
+    }
+    .ok();
+    let remotes = match &git_dir {
+        Some(_) if !app_config.rules.is_empty() => platform::run_command(
+            "git",
+            &[
+                "-C",
+                &dir_arg,
+                "config",
+                "--get-regexp",
+                r"^remote\..*\.url$",
+            ],
+        )
+        .unwrap_or_default(),
+        _ => String::new(),
+    };
+    let urls: Vec<&str> = remotes
+        .lines()
+        .filter_map(|line| line.split_once(' ').map(|(_, url)| url))
+        .collect();
+    matching_profile(app_config, location.as_deref(), &urls)
+}
+
+/// Outside a repository, `location` is the directory itself.
+fn matching_profile(
+    app_config: &AppConfig,
+    location: Option<&str>,
+    urls: &[&str],
+) -> Option<String> {
+    let location = location.map(|location| format!("{}/", location));
+    include_sections(app_config)
+        .into_iter()
+        .rev()
+        .find(|(condition, _)| match condition {
+            IncludeCondition::Gitdir(dir) => location
+                .as_ref()
+                .is_some_and(|location| location.starts_with(&format!("{}/", dir))),
+            IncludeCondition::Remote(pattern) => urls
+                .iter()
+                .any(|url| glob_matches(pattern.as_bytes(), url.as_bytes())),
+        })
+        .map(|(_, profile)| profile.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        glob_matches(pattern.as_bytes(), text.as_bytes())
+    }
+
+    fn git(dir: &Path, global: &Path, args: &[&str]) -> String {
+        let output = std::process::Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .env("GIT_CONFIG_GLOBAL", global)
+            .env("GIT_CONFIG_NOSYSTEM", "1")
+            .output()
+            .unwrap();
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    }
+
+    fn apply_to_file(path: &Path, profile: &GitUserConfig) {
+        write_user_config(
+            &ConfigScope::File(path.to_path_buf()),
+            profile,
+            &BTreeSet::new(),
+        )
+        .unwrap();
+    }
+
+    fn file_value(path: &Path, key: &str) -> Option<String> {
+        git_config(&ConfigScope::File(path.to_path_buf()), &[key]).ok()
+    }
+
+    #[test]
+    fn signing_programs_do_not_leak_between_profiles() {
+        let path = std::env::temp_dir().join(format!("gitup-program-{}", std::process::id()));
+        let signer = GitUserConfig {
+            signing_key: Some("key::ssh-ed25519 AAAA".to_string()),
+            signing_program: Some("/usr/bin/op-ssh-sign".to_string()),
+            ..Default::default()
+        };
+        let corp = GitUserConfig {
+            signing_key: Some("ABCDEF0123456789".to_string()),
+            ..Default::default()
+        };
+        apply_to_file(&path, &signer);
+        assert_eq!(
+            file_value(&path, "gpg.ssh.program").as_deref(),
+            Some("/usr/bin/op-ssh-sign")
+        );
+        apply_to_file(&path, &corp);
+        assert_eq!(file_value(&path, "gpg.ssh.program"), None);
+
+        apply_to_file(&path, &signer);
+        let cleared = GitUserConfig {
+            signing_program: Some(String::new()),
+            ..signer
+        };
+        apply_to_file(&path, &cleared);
+        let program = file_value(&path, "gpg.ssh.program");
+        fs::remove_file(&path).ok();
+        assert_eq!(program, None);
+    }
+
+    #[test]
+    fn signing_is_turned_off_for_profiles_without_a_key() {
+        let path = std::env::temp_dir().join(format!("gitup-signing-{}", std::process::id()));
+        let work = GitUserConfig {
+            email: Some("me@work.com".to_string()),
+            signing_key: Some("ABCDEF0123456789".to_string()),
+            ..Default::default()
+        };
+        let saved_before_policies = GitUserConfig {
+            email: Some("me@home.org".to_string()),
+            ..Default::default()
+        };
+        let saved_untouched = GitUserConfig {
+            signing_policy: Some(SigningPolicy::Untouched),
+            ..saved_before_policies.clone()
+        };
+        let mut values = Vec::new();
+        for home in [saved_before_policies, saved_untouched] {
+            apply_to_file(&path, &work);
+            assert_eq!(file_value(&path, "commit.gpgsign").as_deref(), Some("true"));
+            apply_to_file(&path, &home);
+            values.push(
+                [
+                    "user.signingkey",
+                    "commit.gpgsign",
+                    "tag.gpgSign",
+                    "push.gpgSign",
+                ]
+                .map(|key| file_value(&path, key)),
+            );
+        }
+        fs::remove_file(&path).ok();
+        let off = [
+            None,
+            Some("false".to_string()),
+            Some("false".to_string()),
+            Some("false".to_string()),
+        ];
+        assert_eq!(values, vec![off.clone(), off]);
+    }
+
+    #[test]
+    fn gpgsign_is_read_like_git() {
+        let path = std::env::temp_dir().join(format!("gitup-gpgsign-{}", std::process::id()));
+        let scope = ConfigScope::File(path.clone());
+        let mut policies = Vec::new();
+        for value in ["true", "YES", "on", "1", "false", "No", "off", "0"] {
+            git_config(&scope, &["commit.gpgsign", value]).unwrap();
+            policies.push(read_signing_policy(&scope));
+        }
+        git_config(&scope, &["--unset-all", "commit.gpgsign"]).unwrap();
+        policies.push(read_signing_policy(&scope));
+        fs::remove_file(&path).ok();
+        let (always, never) = (Some(SigningPolicy::Always), Some(SigningPolicy::Never));
+        assert_eq!(
+            policies,
+            vec![always, always, always, always, never, never, never, never, None]
+        );
+    }
+
+    fn profiles(extends: &[(&str, Option<&str>)]) -> AppConfig {
+        let mut app_config = AppConfig::default();
+        for (name, parent) in extends {
+            let profile = GitUserConfig {
+                extends: parent.map(str::to_string),
+                ..Default::default()
+            };
+            app_config.profiles.insert(name.to_string(), profile);
+        }
+        app_config
+    }
+
+    #[test]
+    fn planned_changes_are_listed_once_per_target() {
+        let git = |key: &str, old: Option<&str>, new: Option<&str>| PlannedChange::GitConfig {
+            scope: "global".to_string(),
+            key: key.to_string(),
+            old: old.map(str::to_string),
+            new: new.map(str::to_string),
+        };
+        let backup = PlannedChange::File {
+            path: PathBuf::from("config.json.v1.bak"),
+            old: None,
+            new: Some("{}".to_string()),
+        };
+        take_planned_changes();
+        for _ in 0..3 {
+            record_planned_change(backup.clone());
+        }
+        record_planned_change(git("user.email", Some("a@x.org"), Some("b@x.org")));
+        record_planned_change(git("user.name", Some("A"), Some("B")));
+        record_planned_change(git("user.Email", Some("a@x.org"), Some("c@x.org")));
+        record_planned_change(git("user.name", Some("A"), Some("A")));
+        assert_eq!(
+            take_planned_changes(),
+            [backup, git("user.Email", Some("a@x.org"), Some("c@x.org"))]
+        );
+    }
+
+    #[test]
+    fn drift_lists_only_the_fields_that_differ() {
+        let corp = GitUserConfig {
+            email: Some("me@corp.com".to_string()),
+            signing_key: Some("ABCDEF0123456789".to_string()),
+            ..Default::default()
+        };
+        let applied = GitUserConfig {
+            signing_format: Some(SigningFormat::Openpgp),
+            signing_policy: Some(SigningPolicy::Always),
+            ..corp.clone()
+        };
+        assert!(profile_drift(&corp, &applied).is_empty());
+
+        let home = GitUserConfig {
+            email: Some("me@home.org".to_string()),
+            signing_policy: Some(SigningPolicy::Untouched),
+            ..Default::default()
+        };
+        let live = GitUserConfig {
+            email: Some("me@home.org".to_string()),
+            signing_policy: Some(SigningPolicy::Always),
+            ..Default::default()
+        };
+        assert!(profile_drift(&home, &live).is_empty());
+
+        let signer = GitUserConfig {
+            signing_key: Some("key::ssh-ed25519 AAAA".to_string()),
+            signing_program: Some("/usr/bin/op-ssh-sign".to_string()),
+            signing_policy: Some(SigningPolicy::Always),
+            ..corp
+        };
+        let edited = GitUserConfig {
+            email: Some("me@elsewhere.com".to_string()),
+            signing_format: Some(SigningFormat::Ssh),
+            ..signer.clone()
+        };
+        let fields: Vec<String> = profile_drift(&signer, &edited)
+            .into_iter()
+            .map(|diff| diff.field)
+            .collect();
+        assert_eq!(fields, ["email"]);
+        let unset = GitUserConfig {
+            signing_program: None,
+            ..edited
+        };
+        let fields: Vec<String> = profile_drift(&signer, &unset)
+            .into_iter()
+            .map(|diff| diff.field)
+            .collect();
+        assert_eq!(fields, ["email", "signing program"]);
+    }
+
+    #[test]
+    fn extends_chain_lists_parents_nearest_first() {
+        let app_config = profiles(&[("a", Some("b")), ("b", Some("c")), ("c", None)]);
+        assert_eq!(
+            extends_chain(&app_config, "a").unwrap(),
+            vec!["a", "b", "c"]
+        );
+    }
+
+    #[test]
+    fn extends_chain_rejects_cycles() {
+        let app_config = profiles(&[("a", Some("b")), ("b", Some("a"))]);
+        assert!(matches!(
+            extends_chain(&app_config, "a"),
+            Err(AppError::ProfileCycle(chain)) if chain == "a -> b -> a"
+        ));
+
+        let app_config = profiles(&[("a", Some("a"))]);
+        assert!(matches!(
+            extends_chain(&app_config, "a"),
+            Err(AppError::ProfileCycle(chain)) if chain == "a -> a"
+        ));
+    }
+
+    #[test]
+    fn extends_chain_rejects_missing_parents() {
+        let app_config = profiles(&[("a", Some("gone"))]);
+        assert!(matches!(
+            extends_chain(&app_config, "a"),
+            Err(AppError::ProfileNotFound(name)) if name == "gone"
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn writes_go_through_symlinks() {
+        let root = std::env::temp_dir().join(format!("gitup-symlink-{}", std::process::id()));
+        fs::create_dir_all(root.join("dotfiles")).unwrap();
+        let target = root.join("dotfiles/config.json");
+        let link = root.join("config.json");
+        fs::write(&target, "{}").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        write_private_file(&link, "{\"schemaVersion\": 2}").unwrap();
+        let is_symlink = fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink();
+        let content = fs::read_to_string(&target).unwrap();
+        fs::remove_dir_all(&root).ok();
+        assert!(is_symlink);
+        assert_eq!(content, "{\"schemaVersion\": 2}");
+    }
+
+    fn resolve_with(
+        flag: Option<&str>,
+        vars: &[(&str, &str)],
+        root: &Path,
+    ) -> (PathBuf, ConfigSource) {
+        let vars: HashMap<String, OsString> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), OsString::from(value)))
+            .collect();
+        resolve_config_path_from(
+            flag.map(Path::new),
+            |name| vars.get(name).cloned(),
+            Some(root.join("home")),
+            Some(root.join("platform")),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn config_path_precedence() {
+        let root = std::env::temp_dir().join(format!("gitup-paths-{}", std::process::id()));
+        let env = ("GITUP_CONFIG", "/env/gitup.toml");
+        let xdg = ("XDG_CONFIG_HOME", "/xdg");
+        assert_eq!(
+            resolve_with(Some("/flag/config.json"), &[env, xdg], &root),
+            (PathBuf::from("/flag/config.json"), ConfigSource::Flag)
+        );
+        assert_eq!(
+            resolve_with(None, &[env, xdg], &root),
+            (PathBuf::from("/env/gitup.toml"), ConfigSource::Environment)
+        );
+        assert_eq!(
+            resolve_with(None, &[("GITUP_CONFIG", ""), xdg], &root),
+            (PathBuf::from("/xdg/gitup/config.json"), ConfigSource::Xdg)
+        );
+        assert_eq!(
+            resolve_with(None, &[("XDG_CONFIG_HOME", "relative")], &root),
+            (
+                root.join("platform/gitup/config.json"),
+                ConfigSource::Platform
+            )
+        );
+    }
+
+    #[test]
+    fn legacy_config_is_used_until_the_platform_one_exists() {
+        let root = std::env::temp_dir().join(format!("gitup-legacy-{}", std::process::id()));
+        let legacy = root.join("home/.config/gitup/config.toml");
+        fs::create_dir_all(legacy.parent().unwrap()).unwrap();
+        fs::write(&legacy, "").unwrap();
+        let before = resolve_with(None, &[], &root);
+
+        let platform = root.join("platform/gitup/config.json");
+        fs::create_dir_all(platform.parent().unwrap()).unwrap();
+        fs::write(&platform, "").unwrap();
+        let after = resolve_with(None, &[], &root);
+        fs::remove_dir_all(&root).ok();
+        assert_eq!(before, (legacy, ConfigSource::Legacy));
+        assert_eq!(after, (platform, ConfigSource::Platform));
+    }
+
+    #[test]
+    fn toml_files_keep_their_comments() {
+        let root = std::env::temp_dir().join(format!("gitup-toml-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let path = root.join("config.toml");
+        let original = "# My profiles\nschemaVersion = 2\ncurrentProfile = \"home\" # switched by gitup\n\n# Personal\n[profiles.home]\nname = \"Me\"\nemail = \"me@home.org\"\n\n# Work laptop only\n[profiles.work]\nname = \"Me\"\nemail = \"me@work.com\"\n";
+        fs::write(&path, original).unwrap();
+        let (mut app_config, _, _) = parse_app_config(&path, original).unwrap();
+        app_config.current_profile = Some("work".to_string());
+        app_config.profiles.remove("home");
+        let mut new = app_config.profiles["work"].clone();
+        new.email = Some("me@new.org".to_string());
+        app_config.profiles.insert("new".to_string(), new);
+
+        let content = serialize_for_file(&path, &app_config).unwrap();
+        fs::remove_dir_all(&root).ok();
+        assert_eq!(
+            content,
+            "# My profiles\nschemaVersion = 2\ncurrentProfile = \"work\" # switched by gitup\n\n# Work laptop only\n[profiles.work]\nname = \"Me\"\nemail = \"me@work.com\"\n\n[profiles.new]\nname = \"Me\"\nemail = \"me@new.org\"\n"
+        );
+        let (round_trip, _, _) = parse_app_config(&path, &content).unwrap();
+        assert_eq!(round_trip, app_config);
+    }
+
+    #[test]
+    fn overlapping_bindings_and_rules_resolve_like_git() {
+        let root = std::env::temp_dir().join(format!("gitup-resolve-{}", std::process::id()));
+        fs::remove_dir_all(&root).ok();
+        fs::create_dir_all(root.join("work/team/repo")).unwrap();
+        let root = fs::canonicalize(&root).unwrap();
+        let repo = root.join("work/team/repo");
+        let global = root.join("gitconfig");
+        git(&repo, &global, &["init", "-q"]);
+        let url = "git@github.com:acme/repo.git";
+        git(&repo, &global, &["remote", "add", "origin", url]);
+        let git_dir = git(&repo, &global, &["rev-parse", "--absolute-git-dir"]);
+
+        let mut app_config = AppConfig::default();
+        for name in ["work", "team", "acme"] {
+            let email = format!("{}@example.com", name);
+            fs::write(
+                root.join(format!("{}.gitconfig", name)),
+                format!("[user]\n\temail = {}\n", email),
+            )
+            .unwrap();
+            app_config.profiles.insert(
+                name.to_string(),
+                GitUserConfig {
+                    email: Some(email),
+                    ..Default::default()
+                },
+            );
+        }
+        let dir = |path: &str| root.join(path).display().to_string();
+        app_config
+            .rules
+            .insert("git@github.com:**".to_string(), "work".to_string());
+        app_config
+            .rules
+            .insert("git@github.com:acme/**".to_string(), "acme".to_string());
+        app_config
+            .bindings
+            .insert(dir("work/team"), "team".to_string());
+        app_config.bindings.insert(dir("work"), "work".to_string());
+
+        for expected in ["team", "work", "acme"] {
+            fs::write(&global, "").unwrap();
+            for (condition, profile) in include_sections(&app_config) {
+                let include = root.join(format!("{}.gitconfig", profile));
+                git(
+                    &repo,
+                    &global,
+                    &[
+                        "config",
+                        "--file",
+                        &global.display().to_string(),
+                        &include_key(&condition.section()),
+                        &include.display().to_string(),
+                    ],
+                );
+            }
+            let email = git(&repo, &global, &["config", "user.email"]);
+            let profile = matching_profile(&app_config, Some(&git_dir), &[url]);
+            assert_eq!(profile.as_deref(), Some(expected));
+            assert_eq!(email, format!("{}@example.com", expected));
+
+            let innermost = app_config.bindings.keys().max_by_key(|dir| dir.len());
+            if let Some(dir) = innermost.cloned() {
+                app_config.bindings.remove(&dir);
+            }
+        }
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn ssh_key_round_trips_through_ssh_command() {
+        for path in ["~/.ssh/id_work", "/keys/it's mine", "C:\\keys\\id"] {
+            let command = ssh_command_for_key(path);
+            assert_eq!(ssh_key_from_command(&command).as_deref(), Some(path));
+        }
+        assert_eq!(
+            ssh_command_for_key("/k/it's"),
+            "ssh -i '/k/it'\\''s' -o IdentitiesOnly=yes"
+        );
+    }
+
+    #[test]
+    fn ssh_command_set_by_someone_else_has_no_key() {
+        assert_eq!(ssh_key_from_command("ssh -i ~/.ssh/id_rsa"), None);
+        assert_eq!(
+            ssh_key_from_command("ssh -i '/k' -o IdentitiesOnly=no"),
+            None
+        );
+        assert_eq!(ssh_key_from_command(""), None);
+    }
+
+    #[test]
+    fn glob_single_star_stops_at_slash() {
+        assert!(matches(
+            "git@github.com:acme/*",
+            "git@github.com:acme/repo.git"
+        ));
+        assert!(!matches(
+            "git@github.com:acme/*",
+            "git@github.com:acme/group/repo.git"
+        ));
+        assert!(matches("*.git", "repo.git"));
+        assert!(!matches("*.git", "acme/repo.git"));
+    }
+
+    #[test]
+    fn glob_double_star_crosses_slashes() {
+        assert!(matches(
+            "git@github.com:acme/**",
+            "git@github.com:acme/group/repo.git"
+        ));
+        assert!(matches(
+            "https://**/acme/*",
+            "https://gitlab.example.com/a/b/acme/repo"
+        ));
+        assert!(matches("**", ""));
+        assert!(!matches(
+            "git@github.com:acme/**",
+            "git@github.com:other/repo.git"
+        ));
+    }
+
+    #[test]
+    fn glob_question_mark_matches_one_character_but_not_slash() {
+        assert!(matches("repo?.git", "repo1.git"));
+        assert!(!matches("repo?.git", "repo.git"));
+        assert!(!matches("a?b", "a/b"));
+    }
+
+    #[test]
+    fn glob_literals_must_match_exactly() {
+        assert!(matches("", ""));
+        assert!(!matches("", "a"));
+        assert!(matches("git@host:repo", "git@host:repo"));
+        assert!(!matches("git@host:repo", "git@host:repo.git"));
+    }
 }
@@ -0,0 +1,280 @@
+use crate::domain::{
+    AppConfig, CheckStatus, ConfigScope, Diagnostic, SigningFormat, SigningPolicy,
+};
+use crate::{config, platform};
+use std::fs;
+use std::path::Path;
+
+const OVERRIDE_VARS: [&str; 6] = [
+    "GIT_AUTHOR_NAME",
+    "GIT_AUTHOR_EMAIL",
+    "GIT_COMMITTER_NAME",
+    "GIT_COMMITTER_EMAIL",
+    "GIT_CONFIG_GLOBAL",
+    "GIT_CONFIG_COUNT",
+];
+
+pub fn run_diagnostics() -> Vec<Diagnostic> {
+    let mut diagnostics = vec![check_git_installed()];
+    if diagnostics[0].status == CheckStatus::Fail {
+        return diagnostics;
+    }
+
+    let app_config = check_app_config_file(&mut diagnostics);
+    diagnostics.push(check_identity());
+    diagnostics.push(check_active_profile(app_config.as_ref()));
+    diagnostics.push(check_signing_key());
+    diagnostics.push(check_signing_consistency());
+    diagnostics.push(check_ssh_key());
+    diagnostics.push(check_env_overrides());
+    diagnostics
+}
+
+fn git_value(key: &str) -> Option<String> {
+    platform::run_command("git", &["config", "--global", key])
+        .ok()
+        .filter(|value| !value.is_empty())
+}
+
+fn check_git_installed() -> Diagnostic {
+    const NAME: &str = "Git installation";
+    match platform::run_command("git", &["--version"]) {
+        Ok(version) => Diagnostic::pass(NAME, version),
+        Err(_) => {
+            let hint = platform::get_install_command()
+                .map(|command| format!("Install Git with: {}", command))
+                .unwrap_or_else(|_| "Install Git from https://git-scm.com/downloads.".to_string());
+            Diagnostic::fail(NAME, "Git is not installed.", hint)
+        }
+    }
+}
+
+fn check_app_config_file(diagnostics: &mut Vec<Diagnostic>) -> Option<AppConfig> {
+    const NAME: &str = "GitUp config file";
+    let path = match config::get_app_config_path() {
+        Ok(path) => path,
+        Err(e) => {
+            diagnostics.push(Diagnostic::fail(
+                NAME,
+                e.to_string(),
+                "Make sure the HOME environment variable is set.",
+            ));
+            return None;
+        }
+    };
+    if !path.exists() {
+        diagnostics.push(Diagnostic::pass(
+            NAME,
+            format!("{} does not exist yet.", path.display()),
+        ));
+        return config::load_app_config().ok();
+    }
+
+    let parsed = fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|content| {
+            config::parse_app_config(&path, &content)
+                .and_then(|(mut app_config, _, _)| {
+                    config::read_external_profiles(&path, &mut app_config)?;
+                    Ok(app_config)
+                })
+                .map_err(|e| e.to_string())
+        });
+    let app_config = match parsed {
+        Ok(app_config) => app_config,
+        Err(e) => {
+            diagnostics.push(Diagnostic::fail(
+                NAME,
+                format!("{} could not be parsed: {}", path.display(), e),
+                "Fix the file by hand or move it aside and re-create your profiles.",
+            ));
+            return None;
+        }
+    };
+
+    match platform::file_mode(&path) {
+        Some(mode) if mode & 0o004 != 0 => diagnostics.push(Diagnostic::warn(
+            NAME,
+            format!("{} is world-readable (mode {:o}).", path.display(), mode),
+            format!("Run: chmod 600 {}", path.display()),
+        )),
+        _ => diagnostics.push(Diagnostic::pass(
+            NAME,
+            format!("{} is valid.", path.display()),
+        )),
+    }
+    Some(app_config)
+}
+
+fn check_identity() -> Diagnostic {
+    const NAME: &str = "Global identity";
+    let missing: Vec<&str> = ["user.name", "user.email"]
+        .into_iter()
+        .filter(|key| git_value(key).is_none())
+        .collect();
+    if missing.is_empty() {
+        Diagnostic::pass(NAME, "user.name and user.email are set.")
+    } else {
+        Diagnostic::fail(
+            NAME,
+            format!("Missing {}.", missing.join(" and ")),
+            "Run 'gitup setup' or 'gitup set --name <name> --email <email>'.",
+        )
+    }
+}
+
+fn check_active_profile(app_config: Option<&AppConfig>) -> Diagnostic {
+    const NAME: &str = "Active profile";
+    let Some(app_config) = app_config else {
+        return Diagnostic::warn(
+            NAME,
+            "Skipped because the GitUp config file could not be read.",
+            "Fix the GitUp config file first.",
+        );
+    };
+    let Some(name) = &app_config.current_profile else {
+        if app_config.profiles.is_empty() {
+            return Diagnostic::pass(NAME, "No profiles saved.");
+        }
+        return Diagnostic::warn(
+            NAME,
+            "No profile is active.",
+            "Run 'gitup use' to pick one.",
+        );
+    };
+    if !app_config.profiles.contains_key(name) {
+        return Diagnostic::fail(
+            NAME,
+            format!("Active profile '{}' no longer exists.", name),
+            "Run 'gitup use' to pick an existing profile.",
+        );
+    }
+    let profile = match config::resolve_profile(app_config, name) {
+        Ok(profile) => profile,
+        Err(e) => {
+            return Diagnostic::fail(
+                NAME,
+                e.to_string(),
+                "Fix the 'extends' entries in the GitUp config file.",
+            )
+        }
+    };
+
+    let live = match config::get_git_config(&ConfigScope::Global) {
+        Ok(live) => live,
+        Err(e) => {
+            return Diagnostic::fail(NAME, e.to_string(), "Check that Git can read your config.")
+        }
+    };
+    let mismatched: Vec<String> = config::profile_drift(&profile, &live)
+        .into_iter()
+        .map(|diff| diff.field)
+        .collect();
+
+    if mismatched.is_empty() {
+        Diagnostic::pass(NAME, format!("'{}' matches the live config.", name))
+    } else {
+        Diagnostic::warn(
+            NAME,
+            format!(
+                "Live config differs from profile '{}' in: {}.",
+                name,
+                mismatched.join(", ")
+            ),
+            format!("Run 'gitup use {}' to re-apply it.", name),
+        )
+    }
+}
+
+fn check_signing_key() -> Diagnostic {
+    const NAME: &str = "Signing key";
+    let Some(key) = git_value("user.signingkey") else {
+        return Diagnostic::pass(NAME, "No signing key configured.");
+    };
+    let format = git_value("gpg.format")
+        .and_then(|format| SigningFormat::parse(&format))
+        .unwrap_or(SigningFormat::Openpgp);
+    match platform::check_signing_key(&key, format) {
+        Ok(()) => Diagnostic::pass(
+            NAME,
+            format!("'{}' ({}) is available.", key, format.as_str()),
+        ),
+        Err(reason) => Diagnostic::fail(
+            NAME,
+            format!(
+                "'{}' ({}) could not be found: {}.",
+                key,
+                format.as_str(),
+                reason
+            ),
+            "Import the key, or update it with 'gitup set --signing-key <key>'.",
+        ),
+    }
+}
+
+fn check_signing_consistency() -> Diagnostic {
+    const NAME: &str = "Commit signing";
+    let has_key = git_value("user.signingkey").is_some();
+    let gpgsign = config::read_signing_policy(&ConfigScope::Global) == Some(SigningPolicy::Always);
+    match (has_key, gpgsign) {
+        (true, true) => Diagnostic::pass(NAME, "Commits are signed with the configured key."),
+        (false, false) => Diagnostic::pass(NAME, "Commit signing is off."),
+        (true, false) => Diagnostic::warn(
+            NAME,
+            "A signing key is configured but commit.gpgsign is off.",
+            "Run 'gitup set --signing-policy always' or unset the key with 'gitup set -s \"\"'.",
+        ),
+        (false, true) => Diagnostic::fail(
+            NAME,
+            "commit.gpgsign is on but no signing key is configured.",
+            "Set a key with 'gitup set --signing-key <key>' or run 'gitup set --signing-policy never'.",
+        ),
+    }
+}
+
+fn check_ssh_key() -> Diagnostic {
+    const NAME: &str = "SSH key";
+    let Some(path) =
+        git_value("core.sshCommand").and_then(|command| config::ssh_key_from_command(&command))
+    else {
+        return Diagnostic::pass(NAME, "No profile SSH key configured.");
+    };
+    let expanded = platform::expand_home(Path::new(&path));
+    if !expanded.is_file() {
+        return Diagnostic::fail(
+            NAME,
+            format!("'{}' does not exist.", expanded.display()),
+            "Create the key or update it with 'gitup set --ssh-key <path>'.",
+        );
+    }
+    match platform::check_private_key(&expanded) {
+        Ok(()) => Diagnostic::pass(NAME, format!("'{}' exists with safe permissions.", path)),
+        Err(problem) => Diagnostic::warn(
+            NAME,
+            problem,
+            format!("Run: chmod 600 {}", expanded.display()),
+        ),
+    }
+}
+
+fn check_env_overrides() -> Diagnostic {
+    const NAME: &str = "Environment overrides";
+    let managed_by_hook = std::env::var_os("GITUP_SHELL_PROFILE").is_some();
+    let overrides: Vec<&str> = OVERRIDE_VARS
+        .into_iter()
+        .filter(|var| !(managed_by_hook && *var == "GIT_CONFIG_COUNT"))
+        .filter(|var| std::env::var_os(var).is_some())
+        .collect();
+    if overrides.is_empty() {
+        Diagnostic::pass(NAME, "No environment variables override the Git config.")
+    } else {
+        Diagnostic::warn(
+            NAME,
+            format!("Set in the environment: {}.", overrides.join(", ")),
+            format!(
+                "These take precedence over your profiles. Run: unset {}",
+                overrides.join(" ")
+            ),
+        )
+    }
+}
@@ -1,22 +1,485 @@
+use crate::migrate::CURRENT_SCHEMA_VERSION;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct GitUserConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub email: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub signing_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signing_format: Option<SigningFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signing_program: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signing_policy: Option<SigningPolicy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ssh_key: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extra_config: BTreeMap<String, String>,
+}
+
+impl GitUserConfig {
+    pub fn effective_signing_format(&self) -> Option<SigningFormat> {
+        let key = self.signing_key.as_deref().filter(|key| !key.is_empty())?;
+        Some(
+            self.signing_format
+                .unwrap_or_else(|| SigningFormat::detect(key)),
+        )
+    }
+
+    /// Profiles without an explicit policy sign whenever they have a signing key.
+    pub fn effective_signing_policy(&self) -> SigningPolicy {
+        self.signing_policy.unwrap_or_else(|| {
+            if self.effective_signing_format().is_some() {
+                SigningPolicy::Always
+            } else {
+                SigningPolicy::Never
+            }
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SigningFormat {
+    Openpgp,
+    Ssh,
+    X509,
 }
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+impl SigningFormat {
+    pub const ALL: [SigningFormat; 3] = [
+        SigningFormat::Openpgp,
+        SigningFormat::Ssh,
+        SigningFormat::X509,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SigningFormat::Openpgp => "openpgp",
+            SigningFormat::Ssh => "ssh",
+            SigningFormat::X509 => "x509",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "openpgp" => Some(SigningFormat::Openpgp),
+            "ssh" => Some(SigningFormat::Ssh),
+            "x509" => Some(SigningFormat::X509),
+            _ => None,
+        }
+    }
+
+    pub fn detect(key: &str) -> Self {
+        let key = key.trim();
+        let is_ssh_literal = key.starts_with("key::")
+            || key.starts_with("ssh-")
+            || key.starts_with("ecdsa-")
+            || key.starts_with("sk-");
+        let is_path = key.starts_with('~')
+            || key.starts_with('.')
+            || key.contains('/')
+            || key.contains('\\')
+            || key.ends_with(".pub");
+        if is_ssh_literal || is_path {
+            SigningFormat::Ssh
+        } else {
+            SigningFormat::Openpgp
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SigningPolicy {
+    Always,
+    Never,
+    Untouched,
+}
+
+impl SigningPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SigningPolicy::Always => "always",
+            SigningPolicy::Never => "never",
+            SigningPolicy::Untouched => "untouched",
+        }
+    }
+
+    /// Takes `commit.gpgSign` as printed by `git config --type=bool`.
+    pub fn from_gpgsign(value: &str) -> Self {
+        if value == "true" {
+            SigningPolicy::Always
+        } else {
+            SigningPolicy::Never
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct AppConfig {
-    #[serde(default)]
+    pub schema_version: u64,
+    #[serde(default, serialize_with = "sorted")]
     pub profiles: HashMap<String, GitUserConfig>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub current_profile: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_profile: Option<String>,
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "sorted"
+    )]
+    pub bindings: HashMap<String, String>,
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "sorted"
+    )]
+    pub rules: HashMap<String, String>,
+    #[serde(skip)]
+    pub system_profiles: BTreeSet<String>,
+    #[serde(skip)]
+    pub shadowed_profiles: BTreeSet<String>,
+}
+
+/// Keeps saved files stable so `--dry-run` diffs only show real changes.
+fn sorted<S: serde::Serializer, V: Serialize>(
+    map: &HashMap<String, V>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        AppConfig {
+            schema_version: CURRENT_SCHEMA_VERSION,
+            profiles: HashMap::new(),
+            current_profile: None,
+            previous_profile: None,
+            bindings: HashMap::new(),
+            rules: HashMap::new(),
+            system_profiles: BTreeSet::new(),
+            shadowed_profiles: BTreeSet::new(),
+        }
+    }
+}
+
+impl AppConfig {
+    pub fn switch_profile(&mut self, name: Option<String>) {
+        if self.current_profile != name {
+            if let Some(current) = self.current_profile.take() {
+                self.previous_profile = Some(current);
+            }
+            self.current_profile = name;
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AppState {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_profile: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_profile: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum PlannedChange {
+    GitConfig {
+        scope: String,
+        key: String,
+        old: Option<String>,
+        new: Option<String>,
+    },
+    RemoveSection {
+        scope: String,
+        section: String,
+    },
+    File {
+        path: PathBuf,
+        old: Option<String>,
+        new: Option<String>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    pub timestamp: u64,
+    pub command: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    pub config: GitUserConfig,
+    pub values: BTreeMap<String, Option<String>>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigSource {
+    Flag,
+    Environment,
+    Xdg,
+    Platform,
+    Legacy,
+}
+
+impl ConfigSource {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            ConfigSource::Flag => "from --config",
+            ConfigSource::Environment => "from GITUP_CONFIG",
+            ConfigSource::Xdg => "from XDG_CONFIG_HOME",
+            ConfigSource::Platform => "platform default",
+            ConfigSource::Legacy => "legacy location",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileBundle {
+    pub bundle_version: u64,
+    pub profiles: BTreeMap<String, GitUserConfig>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ImportStrategy {
+    Skip,
+    Overwrite,
+    Rename,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportAction {
+    Add,
+    Unchanged,
+    Skip,
+    Overwrite,
+    Rename,
+}
+
+impl ImportAction {
+    pub fn writes(&self) -> bool {
+        matches!(
+            self,
+            ImportAction::Add | ImportAction::Overwrite | ImportAction::Rename
+        )
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportChange {
+    pub profile: String,
+    pub action: ImportAction,
+    pub target: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    pub const EXTENSIONS: [(&'static str, ConfigFormat); 4] = [
+        ("json", ConfigFormat::Json),
+        ("toml", ConfigFormat::Toml),
+        ("yaml", ConfigFormat::Yaml),
+        ("yml", ConfigFormat::Yaml),
+    ];
+
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        ConfigFormat::EXTENSIONS
+            .iter()
+            .find(|(candidate, _)| *candidate == extension)
+            .map(|(_, format)| *format)
+            .unwrap_or(ConfigFormat::Json)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConfigFormat::Json => "JSON",
+            ConfigFormat::Toml => "TOML",
+            ConfigFormat::Yaml => "YAML",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ConfigFormat::Json => "json",
+            ConfigFormat::Toml => "toml",
+            ConfigFormat::Yaml => "yaml",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigScope {
+    Global,
+    Local,
+    File(PathBuf),
+}
+
+impl ConfigScope {
+    pub fn from_local_flag(local: bool) -> Self {
+        if local {
+            ConfigScope::Local
+        } else {
+            ConfigScope::Global
+        }
+    }
+
+    pub fn args(&self) -> Vec<String> {
+        match self {
+            ConfigScope::Global => vec!["--global".to_string()],
+            ConfigScope::Local => vec!["--local".to_string()],
+            ConfigScope::File(path) => vec!["--file".to_string(), path.display().to_string()],
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            ConfigScope::Global => "global".to_string(),
+            ConfigScope::Local => "local".to_string(),
+            ConfigScope::File(path) => path.display().to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldDiff {
+    pub field: String,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldComparison {
+    pub field: String,
+    pub left: Option<String>,
+    pub right: Option<String>,
+    pub differs: bool,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "state", rename_all = "lowercase")]
+pub enum ProfileStatus {
+    Active {
+        profile: String,
+    },
+    Modified {
+        profile: String,
+        changes: Vec<FieldDiff>,
+    },
+    Unmatched,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostic {
+    pub name: String,
+    pub status: CheckStatus,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn pass(name: &str, message: impl Into<String>) -> Self {
+        Diagnostic {
+            name: name.to_string(),
+            status: CheckStatus::Pass,
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    pub fn warn(name: &str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Diagnostic {
+            name: name.to_string(),
+            status: CheckStatus::Warn,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    pub fn fail(name: &str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Diagnostic {
+            name: name.to_string(),
+            status: CheckStatus::Fail,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_ssh_keys() {
+        for key in [
+            "~/.ssh/id_ed25519.pub",
+            "/home/me/.ssh/signing",
+            "./id.pub",
+            "C:\\Users\\me\\.ssh\\id_ed25519",
+            "id_ed25519.pub",
+            "key::ssh-ed25519 AAAAC3Nza",
+            "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5",
+            "ecdsa-sha2-nistp256 AAAAE2VjZHNh",
+            "sk-ssh-ed25519@openssh.com AAAAGnNr",
+            "  ssh-rsa AAAAB3Nza  ",
+        ] {
+            assert_eq!(SigningFormat::detect(key), SigningFormat::Ssh, "{}", key);
+        }
+    }
+
+    #[test]
+    fn detects_openpgp_key_ids() {
+        for key in [
+            "A1B2C3D4",
+            "0xA1B2C3D4E5F60718",
+            "ABCDEF0123456789ABCDEF0123456789ABCDEF01",
+            "me@example.com",
+        ] {
+            assert_eq!(
+                SigningFormat::detect(key),
+                SigningFormat::Openpgp,
+                "{}",
+                key
+            );
+        }
+    }
 }
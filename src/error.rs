@@ -25,12 +25,119 @@ pub enum AppError {
     #[error("Profile '{0}' not found.")]
     ProfileNotFound(String),
 
+    #[error("No previous profile to switch back to.")]
+    NoPreviousProfile,
+
+    #[error("Profile '{0}' already exists.")]
+    ProfileAlreadyExists(String),
+
+    #[error("Profile inheritance cycle detected: {0}.")]
+    ProfileCycle(String),
+
+    #[error("Profile '{name}' is extended by {children}. Delete those profiles first.")]
+    ProfileExtended { name: String, children: String },
+
+    #[error("Not inside a Git repository.")]
+    NotInGitRepository,
+
+    #[error("'{0}' is managed by GitUp directly and cannot be stored as an extra config key.")]
+    ReservedConfigKey(String),
+
+    #[error("Git config key '{0}' is not set in the global config.")]
+    ConfigKeyNotSet(String),
+
+    #[error("Signing key '{key}' could not be found: {reason}.")]
+    SigningKeyNotFound { key: String, reason: String },
+
+    #[error("Signing key '{key}' could not be found: {reason}. Use --force to set it anyway.")]
+    InvalidSigningKey { key: String, reason: String },
+
+    #[error("Invalid name '{name}': {reason}. Use --force to set it anyway.")]
+    InvalidName { name: String, reason: &'static str },
+
+    #[error("Invalid email '{email}': {reason}. Use --force to set it anyway.")]
+    InvalidEmail { email: String, reason: &'static str },
+
+    #[error("SSH key '{path}' cannot be read: {reason}. Use --force to set it anyway.")]
+    SshKeyUnreadable { path: String, reason: String },
+
+    #[error("Signing policy 'always' requires a signing key. Set one with --signing-key.")]
+    SigningKeyRequired,
+
+    #[error("No profile is bound to directory '{0}'.")]
+    BindingNotFound(String),
+
+    #[error("No rule exists for remote pattern '{0}'.")]
+    RuleNotFound(String),
+
     #[error("Failed to read or write the global GitUp configuration file.")]
     GlobalConfigError(#[source] std::io::Error),
 
+    #[error("The GitUp config file uses schema version {found}, but this version of gitup only supports up to {supported}. Upgrade gitup to read it.")]
+    UnsupportedSchemaVersion { found: u64, supported: u64 },
+
+    #[error("Invalid {format} in the GitUp config file: {message}")]
+    ConfigFormatError {
+        format: &'static str,
+        message: String,
+    },
+
+    #[error("The GitUp config file is already in {0} format.")]
+    ConfigAlreadyInFormat(&'static str),
+
+    #[error("'{0}' already exists. Move it aside before converting.")]
+    ConfigFileExists(String),
+
+    #[error("Invalid profile file '{path}': {message}")]
+    InvalidProfileFile { path: String, message: String },
+
+    #[error("Profile '{0}' is defined by more than one file in profiles.d.")]
+    DuplicateProfileFile(String),
+
+    #[error(
+        "Profile '{0}' cannot be stored in profiles.d because its name is not a valid file name."
+    )]
+    InvalidProfileFileName(String),
+
+    #[error("Profile '{0}' is a system profile and cannot be changed. Copy it to make your own version.")]
+    SystemProfileReadOnly(String),
+
+    #[error("Profiles are already stored in '{0}'.")]
+    ProfilesAlreadySplit(String),
+
+    #[error("Profiles are not stored in a profiles.d directory.")]
+    ProfilesNotSplit,
+
+    #[error("--dry-run is only supported by setup, set, save, use and delete.")]
+    DryRunUnsupported,
+
+    #[error("History entry {0} does not exist. Run 'gitup history' to list them.")]
+    HistoryEntryNotFound(usize),
+
+    #[error("Invalid profile bundle: {0}.")]
+    InvalidBundle(String),
+
     #[error("Failed to serialize or deserialize configuration.")]
     SerializationError(#[from] serde_json::Error),
 
+    #[error("Commit blocked: author email '{actual}' does not match profile '{profile}' (expected '{expected}'). Run 'gitup use {profile} --local' to fix it.")]
+    IdentityMismatch {
+        profile: String,
+        expected: String,
+        actual: String,
+    },
+
+    #[error(
+        "A chained hook already exists at '{0}'. Remove or rename it before installing the guard."
+    )]
+    HookConflict(String),
+
+    #[error("The gitup guard is not installed in this repository.")]
+    GuardNotInstalled,
+
+    #[error("Shell '{0}' is not supported. Use bash, zsh or fish.")]
+    UnsupportedShell(String),
+
     #[error("User cancelled the operation.")]
     OperationCancelled,
 
@@ -0,0 +1,103 @@
+use crate::domain::AppConfig;
+use crate::error::AppError;
+use crate::{config, platform};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const HOOK_NAME: &str = "pre-commit";
+const CHAINED_HOOK_NAME: &str = "pre-commit.gitup-chained";
+const MARKER: &str = "# gitup-guard";
+
+fn hooks_dir() -> Result<PathBuf, AppError> {
+    if !platform::is_inside_git_repository() {
+        return Err(AppError::NotInGitRepository);
+    }
+    match platform::run_command("git", &["config", "core.hooksPath"]) {
+        Ok(path) => {
+            let toplevel = platform::run_command("git", &["rev-parse", "--show-toplevel"])?;
+            Ok(Path::new(&toplevel).join(platform::expand_home(Path::new(&path))))
+        }
+        Err(_) => Ok(PathBuf::from(platform::run_command(
+            "git",
+            &["rev-parse", "--git-path", "hooks"],
+        )?)),
+    }
+}
+
+fn is_guard_hook(path: &Path) -> bool {
+    fs::read_to_string(path).is_ok_and(|content| content.contains(MARKER))
+}
+
+fn hook_script(exe: &str) -> String {
+    format!(
+        r#"#!/bin/sh
+{MARKER}: installed by `gitup guard install`, remove with `gitup guard uninstall`.
+chained="$(dirname "$0")/{CHAINED_HOOK_NAME}"
+if [ -x "$chained" ]; then
+    "$chained" "$@" || exit $?
+fi
+exec '{exe}' guard check
+"#,
+        exe = exe.replace('\'', "'\\''")
+    )
+}
+
+/// Returns the hook path and whether an existing hook is chained.
+pub fn install(exe: &str) -> Result<(PathBuf, bool), AppError> {
+    let dir = hooks_dir()?;
+    fs::create_dir_all(&dir)?;
+    let hook = dir.join(HOOK_NAME);
+    let chained = dir.join(CHAINED_HOOK_NAME);
+
+    if hook.exists() && !is_guard_hook(&hook) {
+        if chained.exists() {
+            return Err(AppError::HookConflict(chained.display().to_string()));
+        }
+        fs::rename(&hook, &chained)?;
+    }
+    fs::write(&hook, hook_script(exe))?;
+    platform::make_executable(&hook)?;
+    Ok((hook, chained.exists()))
+}
+
+pub fn uninstall() -> Result<PathBuf, AppError> {
+    let dir = hooks_dir()?;
+    let hook = dir.join(HOOK_NAME);
+    let chained = dir.join(CHAINED_HOOK_NAME);
+
+    if !is_guard_hook(&hook) {
+        return Err(AppError::GuardNotInstalled);
+    }
+    fs::remove_file(&hook)?;
+    if chained.exists() {
+        fs::rename(&chained, &hook)?;
+    }
+    Ok(hook)
+}
+
+/// Returns the verified profile and email, or `None` when no profile applies.
+pub fn check(app_config: &AppConfig) -> Result<Option<(String, String)>, AppError> {
+    let current_dir = std::env::current_dir()?;
+    let Some(name) = config::resolve_profile_for_dir(app_config, &current_dir) else {
+        return Ok(None);
+    };
+    let Some(expected) = config::resolve_profile(app_config, &name)?.email else {
+        return Ok(None);
+    };
+
+    let ident = platform::run_command("git", &["var", "GIT_AUTHOR_IDENT"]).unwrap_or_default();
+    let actual = ident
+        .split_once('<')
+        .and_then(|(_, rest)| rest.split_once('>'))
+        .map(|(email, _)| email.to_string())
+        .unwrap_or_default();
+
+    if !actual.eq_ignore_ascii_case(&expected) {
+        return Err(AppError::IdentityMismatch {
+            profile: name,
+            expected,
+            actual,
+        });
+    }
+    Ok(Some((name, expected)))
+}
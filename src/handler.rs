@@ -1,32 +1,100 @@
-use crate::{cli, config, domain, error::AppError, platform, ui};
+use crate::domain::ConfigScope;
+use crate::{
+    bundle, cli, config, doctor, domain, error::AppError, guard, platform, shell, ui, validate,
+};
 use anyhow::{anyhow, Result};
 use clap::CommandFactory;
 use clap_complete::{generate, Shell};
 use colored::*;
+use std::collections::HashMap;
 use std::io;
+use std::path::PathBuf;
 
-pub fn handle_command(cli: cli::Cli) -> Result<()> {
+pub fn handle_command(cli: cli::Cli) -> Result<i32> {
+    if let Some(path) = &cli.config {
+        config::set_app_config_path(path)?;
+    }
+    if !cli.dry_run {
+        return dispatch(cli);
+    }
+    if !cli.command.supports_dry_run() {
+        return Err(AppError::DryRunUnsupported.into());
+    }
+    config::set_dry_run();
+    let json = cli.json;
+    let code = dispatch(cli)?;
+    let changes = config::take_planned_changes();
+    if json {
+        println!(
+            "{}",
+            serde_json::json!({"status": "ok", "dryRun": true, "data": { "changes": changes }})
+        );
+    } else {
+        ui::print_planned_changes(&changes);
+    }
+    Ok(code)
+}
+
+fn dispatch(cli: cli::Cli) -> Result<i32> {
     match cli.command {
-        cli::Commands::Check => handle_check(&cli),
+        cli::Commands::Check { local } => handle_check(&cli, local),
+        cli::Commands::Doctor => return handle_doctor(cli.json),
+        cli::Commands::Paths => handle_paths(cli.json),
+        cli::Commands::Config { action } => handle_config_file(action, cli.json),
         cli::Commands::Completions { shell } => handle_completions(shell),
+        cli::Commands::Hook { shell } => handle_hook(shell),
+        cli::Commands::Env { shell } => handle_env(shell),
         _ => {
             if !platform::is_git_installed() {
-                return handle_git_not_installed(&cli);
+                return handle_git_not_installed(&cli).map(|()| 0);
             }
             match cli.command {
                 cli::Commands::Setup(args) => handle_setup(args, cli.json),
                 cli::Commands::Set(args) => handle_set(args, cli.json),
-                cli::Commands::Save { name } => handle_save_profile(name, cli.json),
-                cli::Commands::Use { name } => handle_use_profile(name, cli.json),
+                cli::Commands::Save {
+                    name,
+                    keys,
+                    extends,
+                } => handle_save_profile(name, keys, extends, cli.json),
+                cli::Commands::Show { name, resolved } => {
+                    handle_show_profile(name, resolved, cli.json)
+                }
+                cli::Commands::Diff { profile, other } => {
+                    return handle_diff(profile, other, cli.json)
+                }
+                cli::Commands::Use { name, local } => handle_use_profile(name, local, cli.json),
                 cli::Commands::List => handle_list_profiles(cli.json),
-                cli::Commands::Current => handle_current_profile(cli.json),
+                cli::Commands::Current { detect } => handle_current_profile(detect, cli.json),
+                cli::Commands::History => handle_history(cli.json),
+                cli::Commands::Undo { n } => handle_undo(n, cli.json),
                 cli::Commands::Delete { name, force } => {
                     handle_delete_profile(name, force, cli.json)
                 }
+                cli::Commands::Rename { old, new } => handle_rename_profile(old, new, cli.json),
+                cli::Commands::Copy {
+                    source,
+                    destination,
+                } => handle_copy_profile(source, destination, cli.json),
+                cli::Commands::Edit(args) => handle_edit_profile(args, cli.json),
+                cli::Commands::Export {
+                    names,
+                    strip_signing_keys,
+                } => handle_export(names, strip_signing_keys),
+                cli::Commands::Import {
+                    file,
+                    strategy,
+                    preview,
+                    yes,
+                } => handle_import(file, strategy, preview, yes, cli.json),
+                cli::Commands::Bind { dir, profile } => handle_bind(dir, profile, cli.json),
+                cli::Commands::Unbind { dir } => handle_unbind(dir, cli.json),
+                cli::Commands::Rules { action } => handle_rules(action, cli.json),
+                cli::Commands::Guard { action } => handle_guard(action, cli.json),
                 _ => unreachable!(),
             }
         }
     }
+    .map(|()| 0)
 }
 
 fn handle_git_not_installed(cli: &cli::Cli) -> Result<()> {
@@ -48,7 +116,7 @@ fn handle_git_not_installed(cli: &cli::Cli) -> Result<()> {
 }
 
 fn handle_setup(args: cli::SetupArgs, json: bool) -> Result<()> {
-    let current_config = config::get_git_config()?;
+    let current_config = config::get_git_config(&ConfigScope::Global)?;
     if !json {
         let app_config = config::load_app_config()?;
         ui::print_status(&current_config, Some(&app_config));
@@ -69,14 +137,48 @@ fn handle_setup(args: cli::SetupArgs, json: bool) -> Result<()> {
         return Err(anyhow!("Cannot run interactive setup with --non-interactive and --json flags. Provide values via the 'set' command."));
     }
 
-    let name = ui::prompt_for_input("Enter your Git user name:", current_config.name.as_deref())?;
-    let email = ui::prompt_for_input("Enter your Git email:", current_config.email.as_deref())?;
-    let signing_key = ui::prompt_for_optional_input(
+    let force = args.force;
+    let check = |input: &str, validate: fn(&str) -> Result<String, AppError>| {
+        if force {
+            Ok(input.to_string())
+        } else {
+            validate(input)
+        }
+    };
+    let name = ui::prompt_for_valid_input(
+        "Enter your Git user name:",
+        current_config.name.as_deref(),
+        |input| check(input, validate::name),
+    )?;
+    let email = ui::prompt_for_valid_input(
+        "Enter your Git email:",
+        current_config.email.as_deref(),
+        |input| check(input, validate::email),
+    )?;
+    let signing_key = ui::prompt_for_valid_input(
         "Enter your GPG/SSH signing key (optional):",
         current_config.signing_key.as_deref(),
+        |input| {
+            if input.trim().is_empty() {
+                Ok(String::new())
+            } else if current_config.signing_key.as_deref() == Some(input) {
+                Ok(input.to_string())
+            } else {
+                check(input, validate::signing_key)
+            }
+        },
     )?;
 
+    let key_unchanged = current_config.signing_key.as_deref().unwrap_or("") == signing_key;
+    let signing_format = if signing_key.is_empty() {
+        None
+    } else if key_unchanged {
+        current_config.effective_signing_format()
+    } else {
+        Some(domain::SigningFormat::detect(&signing_key))
+    };
     let new_config = domain::GitUserConfig {
+        extends: None,
         name: Some(name),
         email: Some(email),
         signing_key: if signing_key.is_empty() {
@@ -84,8 +186,28 @@ fn handle_setup(args: cli::SetupArgs, json: bool) -> Result<()> {
         } else {
             Some(signing_key)
         },
+        signing_format,
+        signing_program: if key_unchanged {
+            current_config.signing_program.clone()
+        } else {
+            None
+        },
+        signing_policy: if key_unchanged {
+            Some(domain::SigningPolicy::Untouched)
+        } else {
+            None
+        },
+        ssh_key: current_config.ssh_key.clone(),
+        extra_config: current_config.extra_config.clone(),
     };
-    config::set_git_config(&new_config)?;
+    if let (false, Some(format)) = (json, signing_format) {
+        println!("Detected signing format: {}", format.as_str().cyan());
+    }
+    config::record_history("setup")?;
+    config::set_git_config(&new_config, &ConfigScope::Global)?;
+    if config::is_dry_run() {
+        return Ok(());
+    }
 
     if json {
         let app_config = config::load_app_config()?;
@@ -104,7 +226,7 @@ fn handle_setup(args: cli::SetupArgs, json: bool) -> Result<()> {
                 None,
             )?;
             if !profile_name.trim().is_empty() {
-                handle_save_profile(profile_name, json)?;
+                handle_save_profile(profile_name, Vec::new(), None, json)?;
             } else {
                 println!("{}", "Info: Profile not saved due to empty name.".yellow());
             }
@@ -113,35 +235,225 @@ fn handle_setup(args: cli::SetupArgs, json: bool) -> Result<()> {
     Ok(())
 }
 
-fn handle_check(cli: &cli::Cli) -> Result<()> {
+fn handle_check(cli: &cli::Cli, local: bool) -> Result<()> {
     if !platform::is_git_installed() {
         return handle_git_not_installed(cli);
     }
-    let git_config = config::get_git_config()?;
+    let (git_config, origins) = if local {
+        let git_config = config::get_git_config(&ConfigScope::Local)?;
+        let origins = [
+            ("user.name", git_config.name.is_some()),
+            ("user.email", git_config.email.is_some()),
+            ("user.signingkey", git_config.signing_key.is_some()),
+            ("commit.gpgsign", git_config.signing_policy.is_some()),
+            ("core.sshCommand", git_config.ssh_key.is_some()),
+        ]
+        .into_iter()
+        .filter(|(_, is_set)| *is_set)
+        .map(|(key, _)| key.to_string())
+        .chain(git_config.extra_config.keys().cloned())
+        .map(|key| (key, "local".to_string()))
+        .collect();
+        (git_config, origins)
+    } else {
+        config::get_effective_git_config()?
+    };
+    let local_profile = config::get_local_profile();
     let app_config = config::load_app_config()?;
+    let status = config::profile_status(&app_config)?;
     if cli.json {
-        ui::print_json_status(&git_config, &app_config);
+        ui::print_json_scoped_status(
+            &git_config,
+            &origins,
+            &app_config,
+            &status,
+            local_profile.as_deref(),
+        );
     } else {
-        ui::print_status(&git_config, Some(&app_config));
+        ui::print_scoped_status(&git_config, &origins, None);
+        if app_config.current_profile.is_some() {
+            ui::print_profile_status(&status);
+        }
+        if let Some(profile) = &local_profile {
+            ui::print_local_profile(profile);
+        }
+    }
+    Ok(())
+}
+
+fn handle_doctor(json: bool) -> Result<i32> {
+    let diagnostics = doctor::run_diagnostics();
+    if json {
+        ui::print_json_diagnostics(&diagnostics);
+    } else {
+        ui::print_diagnostics(&diagnostics);
+    }
+    let failed = diagnostics
+        .iter()
+        .any(|d| d.status == domain::CheckStatus::Fail);
+    Ok(if failed { 1 } else { 0 })
+}
+
+fn handle_paths(json: bool) -> Result<()> {
+    let (config_file, source) = config::resolve_app_config_path()?;
+    let lock_file = config::get_lock_path()?;
+    let includes_dir = config::get_includes_dir()?;
+    let git_config = config::get_global_git_config_path()?;
+    let profiles_dir = config::get_profiles_dir()?;
+    let profile_store = profiles_dir
+        .is_dir()
+        .then(|| config::get_state_path().map(|state_file| (profiles_dir, state_file)))
+        .transpose()?;
+    let system_dir = config::get_system_profiles_dir();
+    if json {
+        let (profiles_dir, state_file) = profile_store.unzip();
+        println!(
+            "{}",
+            serde_json::json!({
+                "status": "ok",
+                "data": {
+                    "configFile": config_file,
+                    "source": source,
+                    "lockFile": lock_file,
+                    "includesDir": includes_dir,
+                    "profilesDir": profiles_dir,
+                    "stateFile": state_file,
+                    "systemProfilesDir": system_dir,
+                    "gitConfig": git_config
+                }
+            })
+        );
+    } else {
+        ui::print_paths(
+            &config_file,
+            source,
+            &lock_file,
+            &includes_dir,
+            profile_store
+                .as_ref()
+                .map(|(dir, state)| (dir.as_path(), state.as_path())),
+            &system_dir,
+            &git_config,
+        );
+    }
+    Ok(())
+}
+
+fn handle_config_file(action: cli::ConfigCommand, json: bool) -> Result<()> {
+    let (message, moved) = match action {
+        cli::ConfigCommand::Convert { to } => {
+            let (target, backup) = config::convert_app_config(to)?;
+            let message = format!(
+                "Converted the config file to '{}'. The original was kept as '{}'.",
+                target.display(),
+                backup.display()
+            );
+            (message, true)
+        }
+        cli::ConfigCommand::Split => {
+            let dir = config::split_profiles()?;
+            (
+                format!(
+                    "Moved every profile into its own file in '{}'.",
+                    dir.display()
+                ),
+                false,
+            )
+        }
+        cli::ConfigCommand::Join => {
+            let path = config::join_profiles()?;
+            (
+                format!("Moved every profile back into '{}'.", path.display()),
+                false,
+            )
+        }
+    };
+    if json {
+        println!(
+            "{}",
+            serde_json::json!({"status": "ok", "message": message})
+        );
+    } else {
+        ui::print_success(&message);
+    }
+    let (_, source) = config::resolve_app_config_path()?;
+    let explicit = matches!(
+        source,
+        domain::ConfigSource::Flag | domain::ConfigSource::Environment
+    );
+    if moved && explicit && !json {
+        println!(
+            "{}",
+            "Update --config or GITUP_CONFIG to point at the new file.".yellow()
+        );
     }
     Ok(())
 }
 
 fn handle_set(args: cli::ConfigArgs, json: bool) -> Result<()> {
-    let mut config_to_set = config::get_git_config()?;
+    let scope = ConfigScope::from_local_flag(args.local);
+    let mut config_to_set = config::get_git_config(&scope)?;
+    // The live policy only reflects commit.gpgSign, so writing it back would
+    // also overwrite tag.gpgSign and push.gpgSign.
+    config_to_set.signing_policy = Some(domain::SigningPolicy::Untouched);
+    let force = args.force;
+    let check = |value: String, validate: fn(&str) -> Result<String, AppError>| {
+        if force {
+            Ok(value)
+        } else {
+            validate(&value)
+        }
+    };
 
     if let Some(name) = args.name {
-        config_to_set.name = Some(name);
+        config_to_set.name = Some(check(name, validate::name)?);
     }
     if let Some(email) = args.email {
-        config_to_set.email = Some(email);
+        config_to_set.email = Some(check(email, validate::email)?);
     }
+    let signing_changed = args.signing_key.is_some()
+        || args.signing_format.is_some()
+        || args.signing_program.is_some()
+        || args.signing_policy.is_some();
     if let Some(key) = args.signing_key {
         config_to_set.signing_key = if key.is_empty() { None } else { Some(key) };
+        config_to_set.signing_format = None;
+        config_to_set.signing_policy = None;
+    }
+    if let Some(format) = args.signing_format {
+        config_to_set.signing_format = Some(format);
+    }
+    if let Some(program) = args.signing_program {
+        config_to_set.signing_program = if program.is_empty() {
+            None
+        } else {
+            Some(program)
+        };
+    }
+    if let Some(policy) = args.signing_policy {
+        config_to_set.signing_policy = Some(policy);
+    }
+    config_to_set.signing_format = config_to_set.effective_signing_format();
+    if let Some(path) = args.ssh_key {
+        config_to_set.ssh_key = if path.is_empty() {
+            None
+        } else {
+            Some(check(path, validate::ssh_key)?)
+        };
     }
 
-    config::set_git_config(&config_to_set)?;
-    let final_config = config::get_git_config()?;
+    if signing_changed && !force {
+        validate::signing_config(&config_to_set)?;
+    }
+
+    if scope == ConfigScope::Global {
+        config::record_history("set")?;
+    }
+    config::set_git_config(&config_to_set, &scope)?;
+    if config::is_dry_run() {
+        return Ok(());
+    }
+    let final_config = config::get_git_config(&scope)?;
     let app_config = config::load_app_config()?;
     if json {
         ui::print_json_status(&final_config, &app_config);
@@ -152,8 +464,13 @@ fn handle_set(args: cli::ConfigArgs, json: bool) -> Result<()> {
     Ok(())
 }
 
-fn handle_save_profile(name: String, json: bool) -> Result<()> {
-    let git_config = config::get_git_config()?;
+fn handle_save_profile(
+    name: String,
+    keys: Vec<String>,
+    extends: Option<String>,
+    json: bool,
+) -> Result<()> {
+    let git_config = config::snapshot_git_config(&name, &keys)?;
     if git_config.name.as_deref().unwrap_or("").is_empty()
         || git_config.email.as_deref().unwrap_or("").is_empty()
     {
@@ -161,7 +478,21 @@ fn handle_save_profile(name: String, json: bool) -> Result<()> {
             "Current Git config is incomplete (name or email is missing). Cannot save profile."
         ));
     }
+    let app_config = config::load_app_config()?;
+    let extends = extends.or_else(|| {
+        app_config
+            .profiles
+            .get(&name)
+            .and_then(|profile| profile.extends.clone())
+    });
+    let git_config = match extends {
+        Some(base) => config::strip_inherited(&app_config, &base, &git_config)?,
+        None => git_config,
+    };
     config::save_profile(&name, &git_config)?;
+    if config::is_dry_run() {
+        return Ok(());
+    }
     if json {
         println!(
             "{}",
@@ -173,8 +504,40 @@ fn handle_save_profile(name: String, json: bool) -> Result<()> {
     Ok(())
 }
 
-fn handle_use_profile(name: Option<String>, json: bool) -> Result<()> {
+fn handle_diff(profile: String, other: Option<String>, json: bool) -> Result<i32> {
+    let app_config = config::load_app_config()?;
+    let left = config::resolve_profile(&app_config, &profile)?;
+    let right = match &other {
+        Some(other) => config::resolve_profile(&app_config, other)?,
+        None => config::get_git_config(&ConfigScope::Global)?,
+    };
+    let comparison = config::compare_configs(&left, &right);
+    let differing = comparison.iter().filter(|row| row.differs).count();
+    if json {
+        println!(
+            "{}",
+            serde_json::json!({
+                "status": "ok",
+                "data": {
+                    "left": profile,
+                    "right": other,
+                    "differs": differing > 0,
+                    "fields": comparison
+                }
+            })
+        );
+    } else {
+        ui::print_comparison(&profile, other.as_deref(), &comparison);
+    }
+    Ok(if differing > 0 { 1 } else { 0 })
+}
+
+fn handle_use_profile(name: Option<String>, local: bool, json: bool) -> Result<()> {
+    let scope = ConfigScope::from_local_flag(local);
     let profile_name = match name {
+        Some(n) if n == "-" => config::load_app_config()?
+            .previous_profile
+            .ok_or(AppError::NoPreviousProfile)?,
         Some(n) => n,
         None => {
             if json {
@@ -189,48 +552,149 @@ fn handle_use_profile(name: Option<String>, json: bool) -> Result<()> {
         }
     };
 
-    let new_config = config::use_profile(&profile_name)?;
+    let new_config = config::use_profile(&profile_name, &scope)?;
+    if config::is_dry_run() {
+        return Ok(());
+    }
+    let app_config = config::load_app_config()?;
     if json {
-        let app_config = config::load_app_config()?;
         ui::print_json_status(&new_config, &app_config);
+    } else if scope == ConfigScope::Local {
+        ui::print_success(&format!(
+            "Applied profile '{}' to the current repository.",
+            profile_name
+        ));
+        ui::print_status(&new_config, None);
+        ui::print_local_profile(&profile_name);
     } else {
         ui::print_success(&format!("Switched to profile '{}'.", profile_name));
-        let app_config = config::load_app_config()?;
         ui::print_status(&new_config, Some(&app_config));
     }
     Ok(())
 }
 
+fn handle_show_profile(name: String, resolved: bool, json: bool) -> Result<()> {
+    let app_config = config::load_app_config()?;
+    let stored = app_config
+        .profiles
+        .get(&name)
+        .ok_or_else(|| AppError::ProfileNotFound(name.clone()))?;
+    let (profile, origins) = if resolved {
+        config::resolve_profile_with_origins(&app_config, &name)?
+    } else {
+        (stored.clone(), HashMap::new())
+    };
+    if json {
+        ui::print_json_profile(&name, &profile, &origins);
+    } else {
+        ui::print_profile(&name, stored.extends.as_deref(), &profile, &origins);
+    }
+    Ok(())
+}
+
 fn handle_list_profiles(json: bool) -> Result<()> {
     let app_config = config::load_app_config()?;
+    let status = config::profile_status(&app_config)?;
     if json {
-        ui::print_json_profiles(&app_config);
+        ui::print_json_profiles(&app_config, &status);
     } else {
-        ui::print_profiles(&app_config);
+        ui::print_profiles(&app_config, &status);
+        for name in &app_config.shadowed_profiles {
+            ui::print_warning(&format!(
+                "Your profile '{}' shadows the system profile of the same name.",
+                name
+            ));
+        }
     }
     Ok(())
 }
 
-fn handle_current_profile(json: bool) -> Result<()> {
+fn handle_current_profile(detect: bool, json: bool) -> Result<()> {
     let app_config = config::load_app_config()?;
+    if detect {
+        let matches = config::detect_profiles(&app_config)?;
+        if json {
+            println!(
+                "{}",
+                serde_json::json!({"status": "ok", "data": { "matches": matches }})
+            );
+        } else if matches.is_empty() {
+            println!("No saved profile matches the live Git config.");
+        } else {
+            println!("The live Git config matches: {}", matches.join(", ").cyan());
+        }
+        return Ok(());
+    }
+
+    let status = config::profile_status(&app_config)?;
     if json {
         println!(
             "{}",
             serde_json::json!({
                 "status": "ok",
-                "data": { "activeProfile": app_config.current_profile }
+                "data": {
+                    "activeProfile": app_config.current_profile,
+                    "previousProfile": app_config.previous_profile,
+                    "profileStatus": status
+                }
             })
         );
-    } else if let Some(profile) = app_config.current_profile {
-        println!("Active profile: {}", profile.cyan());
     } else {
-        println!("No profile is currently active.");
+        if app_config.current_profile.is_none() {
+            println!("No profile is currently active.");
+        } else {
+            ui::print_profile_status(&status);
+        }
+        if let Some(previous) = &app_config.previous_profile {
+            println!("  {:<12}: {}", "Previous".green(), previous);
+        }
+    }
+    Ok(())
+}
+
+fn handle_history(json: bool) -> Result<()> {
+    let history = config::load_history()?;
+    if json {
+        println!(
+            "{}",
+            serde_json::json!({"status": "ok", "data": { "entries": history }})
+        );
+    } else {
+        ui::print_history(&history);
+    }
+    Ok(())
+}
+
+fn handle_undo(n: usize, json: bool) -> Result<()> {
+    let history = config::load_history()?;
+    let entry = n
+        .checked_sub(1)
+        .and_then(|index| history.into_iter().nth(index))
+        .ok_or(AppError::HistoryEntryNotFound(n))?;
+    config::record_history(&format!("undo {}", n))?;
+    config::restore_git_config(&entry.values)?;
+    let app_config = config::update_app_config(|app_config| {
+        let profile = entry
+            .profile
+            .clone()
+            .filter(|name| app_config.profiles.contains_key(name));
+        app_config.switch_profile(profile);
+        Ok(app_config.clone())
+    })?;
+
+    let restored = config::get_git_config(&ConfigScope::Global)?;
+    let message = format!("Restored the identity from before '{}'.", entry.command);
+    if json {
+        ui::print_json_status(&restored, &app_config);
+    } else {
+        ui::print_success(&message);
+        ui::print_status(&restored, Some(&app_config));
     }
     Ok(())
 }
 
 fn handle_delete_profile(name: String, force: bool, json: bool) -> Result<()> {
-    if !json && !force {
+    if !json && !force && !config::is_dry_run() {
         let confirmation_prompt =
             format!("Are you sure you want to delete the profile '{}'?", name);
         if !ui::confirm(&confirmation_prompt, false)? {
@@ -239,29 +703,358 @@ fn handle_delete_profile(name: String, force: bool, json: bool) -> Result<()> {
         }
     }
 
-    let mut app_config = config::load_app_config()?;
-    if app_config.profiles.remove(&name).is_some() {
+    config::update_app_config(|app_config| {
+        config::ensure_user_profile(app_config, &name)?;
+        let children = config::profile_children(app_config, &name);
+        if !children.is_empty() {
+            return Err(AppError::ProfileExtended {
+                name: name.clone(),
+                children: children.join(", "),
+            });
+        }
+        if app_config.profiles.remove(&name).is_none() {
+            return Err(AppError::ProfileNotFound(name.clone()));
+        }
         if app_config.current_profile.as_ref() == Some(&name) {
             app_config.current_profile = None;
         }
-        config::save_app_config(&app_config)?;
-        if json {
-            println!(
-                "{}",
-                serde_json::json!({"status": "ok", "message": format!("Profile '{}' deleted.", name)})
+        if app_config.previous_profile.as_ref() == Some(&name) {
+            app_config.previous_profile = None;
+        }
+        config::detach_profile(app_config, &name)
+    })?;
+    if config::is_dry_run() {
+        return Ok(());
+    }
+    if json {
+        println!(
+            "{}",
+            serde_json::json!({"status": "ok", "message": format!("Profile '{}' deleted.", name)})
+        );
+    } else {
+        ui::print_success(&format!("Profile '{}' has been deleted.", name));
+    }
+    Ok(())
+}
+
+fn handle_rename_profile(old: String, new: String, json: bool) -> Result<()> {
+    config::rename_profile(&old, &new)?;
+    let message = format!("Profile '{}' renamed to '{}'.", old, new);
+    if json {
+        println!(
+            "{}",
+            serde_json::json!({"status": "ok", "message": message})
+        );
+    } else {
+        ui::print_success(&message);
+    }
+    Ok(())
+}
+
+fn handle_copy_profile(source: String, destination: String, json: bool) -> Result<()> {
+    config::copy_profile(&source, &destination)?;
+    let message = format!("Profile '{}' copied to '{}'.", source, destination);
+    if json {
+        println!(
+            "{}",
+            serde_json::json!({"status": "ok", "message": message})
+        );
+    } else {
+        ui::print_success(&message);
+    }
+    Ok(())
+}
+
+fn handle_edit_profile(args: cli::EditArgs, json: bool) -> Result<()> {
+    let name = args.profile;
+    let app_config = config::update_app_config(|app_config| {
+        config::ensure_user_profile(app_config, &name)?;
+        let profile = app_config
+            .profiles
+            .get_mut(&name)
+            .ok_or_else(|| AppError::ProfileNotFound(name.clone()))?;
+
+        let empty_to_none = |value: String| if value.is_empty() { None } else { Some(value) };
+        if let Some(user_name) = args.name {
+            profile.name = empty_to_none(user_name);
+        }
+        if let Some(email) = args.email {
+            profile.email = empty_to_none(email);
+        }
+        let force = args.force;
+        let signing_changed = args.signing_key.is_some()
+            || args.signing_format.is_some()
+            || args.signing_program.is_some()
+            || args.signing_policy.is_some();
+        if let Some(key) = args.signing_key {
+            profile.signing_key = empty_to_none(key);
+            profile.signing_format = None;
+            profile.signing_policy = None;
+        }
+        if let Some(format) = args.signing_format {
+            profile.signing_format = Some(format);
+        }
+        if let Some(program) = args.signing_program {
+            profile.signing_program = empty_to_none(program);
+        }
+        if let Some(policy) = args.signing_policy {
+            profile.signing_policy = Some(policy);
+        }
+        if let Some(path) = args.ssh_key {
+            profile.ssh_key = empty_to_none(path);
+        }
+        if let Some(base) = args.extends {
+            profile.extends = empty_to_none(base);
+        }
+
+        let profile = profile.clone();
+        if signing_changed && !force {
+            validate::signing_config(&config::resolve_profile(app_config, &name)?)?;
+        }
+        config::store_profile(app_config, &name, &profile)?;
+        Ok(app_config.clone())
+    })?;
+
+    let active = app_config
+        .current_profile
+        .as_deref()
+        .filter(|current| config::profile_depends_on(&app_config, current, &name));
+    if let Some(current) = active {
+        let resolved = config::resolve_profile(&app_config, current)?;
+        config::record_history(&format!("edit {}", name))?;
+        config::set_git_config(&resolved, &ConfigScope::Global)?;
+    }
+
+    let message = match active {
+        Some(current) if current == name => {
+            format!("Profile '{}' updated and re-applied.", name)
+        }
+        Some(current) => format!(
+            "Profile '{}' updated and re-applied through active profile '{}', which extends it.",
+            name, current
+        ),
+        None => format!("Profile '{}' updated.", name),
+    };
+    if json {
+        println!(
+            "{}",
+            serde_json::json!({"status": "ok", "message": message})
+        );
+    } else {
+        ui::print_success(&message);
+        let profile = &app_config.profiles[&name];
+        ui::print_profile(&name, profile.extends.as_deref(), profile, &HashMap::new());
+    }
+    Ok(())
+}
+
+fn handle_export(names: Vec<String>, strip_signing_keys: bool) -> Result<()> {
+    let app_config = config::load_app_config()?;
+    let bundle = bundle::export(&app_config, &names, strip_signing_keys)?;
+    println!("{}", serde_json::to_string_pretty(&bundle)?);
+    Ok(())
+}
+
+fn handle_import(
+    file: PathBuf,
+    strategy: domain::ImportStrategy,
+    preview: bool,
+    yes: bool,
+    json: bool,
+) -> Result<()> {
+    let bundle = bundle::read(&file)?;
+    let mut changes = bundle::plan(&config::load_app_config()?, &bundle, strategy);
+    let writes = changes.iter().any(|change| change.action.writes());
+    if !json {
+        ui::print_import_preview(&changes);
+    }
+
+    let apply =
+        !preview && writes && (json || yes || ui::confirm("\nImport these profiles?", true)?);
+    if apply {
+        changes = config::update_app_config(|app_config| {
+            let changes = bundle::plan(app_config, &bundle, strategy);
+            bundle::apply(app_config, &bundle, &changes)?;
+            Ok(changes)
+        })?;
+    }
+
+    if json {
+        println!(
+            "{}",
+            serde_json::json!({
+                "status": "ok",
+                "data": { "applied": apply, "changes": changes }
+            })
+        );
+    } else if apply {
+        let count = changes
+            .iter()
+            .filter(|change| change.action.writes())
+            .count();
+        ui::print_success(&format!("Imported {} profile(s).", count));
+    } else if !writes {
+        println!("Nothing to import.");
+    } else if !preview {
+        println!("Import cancelled.");
+    }
+    Ok(())
+}
+
+fn handle_bind(dir: PathBuf, profile: String, json: bool) -> Result<()> {
+    let dir = config::bind_directory(&dir, &profile)?;
+    let message = format!(
+        "Repositories under '{}/' will now use profile '{}'.",
+        dir, profile
+    );
+    if json {
+        println!(
+            "{}",
+            serde_json::json!({"status": "ok", "message": message})
+        );
+    } else {
+        ui::print_success(&message);
+    }
+    Ok(())
+}
+
+fn handle_unbind(dir: PathBuf, json: bool) -> Result<()> {
+    let (dir, profile) = config::unbind_directory(&dir)?;
+    let message = format!(
+        "Directory '{}/' is no longer bound to profile '{}'.",
+        dir, profile
+    );
+    if json {
+        println!(
+            "{}",
+            serde_json::json!({"status": "ok", "message": message})
+        );
+    } else {
+        ui::print_success(&message);
+    }
+    Ok(())
+}
+
+fn handle_rules(action: cli::RulesCommand, json: bool) -> Result<()> {
+    match action {
+        cli::RulesCommand::Add { pattern, profile } => {
+            config::add_remote_rule(&pattern, &profile)?;
+            let message = format!(
+                "Repositories with a remote matching '{}' will now use profile '{}'.",
+                pattern, profile
             );
-        } else {
-            ui::print_success(&format!("Profile '{}' has been deleted.", name));
+            if json {
+                println!(
+                    "{}",
+                    serde_json::json!({"status": "ok", "message": message})
+                );
+            } else {
+                ui::print_success(&message);
+            }
+        }
+        cli::RulesCommand::List => {
+            let app_config = config::load_app_config()?;
+            if json {
+                ui::print_json_rules(&app_config);
+            } else {
+                ui::print_rules(&app_config);
+            }
+        }
+        cli::RulesCommand::Remove { pattern } => {
+            let profile = config::remove_remote_rule(&pattern)?;
+            let message = format!(
+                "Rule '{}' for profile '{}' has been removed.",
+                pattern, profile
+            );
+            if json {
+                println!(
+                    "{}",
+                    serde_json::json!({"status": "ok", "message": message})
+                );
+            } else {
+                ui::print_success(&message);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn handle_guard(action: cli::GuardCommand, json: bool) -> Result<()> {
+    let message = match action {
+        cli::GuardCommand::Install { profile } => {
+            if let Some(profile) = &profile {
+                let app_config = config::load_app_config()?;
+                if !app_config.profiles.contains_key(profile) {
+                    return Err(AppError::ProfileNotFound(profile.clone()).into());
+                }
+                config::pin_local_profile(profile)?;
+            }
+            let (hook, chained) = guard::install(&current_exe())?;
+            if chained {
+                format!(
+                    "Guard installed at '{}'. The existing hook will keep running first.",
+                    hook.display()
+                )
+            } else {
+                format!("Guard installed at '{}'.", hook.display())
+            }
+        }
+        cli::GuardCommand::Uninstall => {
+            let hook = guard::uninstall()?;
+            format!("Guard removed from '{}'.", hook.display())
         }
+        cli::GuardCommand::Check => {
+            let app_config = config::load_app_config()?;
+            match guard::check(&app_config)? {
+                Some((profile, email)) => {
+                    format!("Committing as '{}' <{}>.", profile, email)
+                }
+                None => "No profile applies to this repository.".to_string(),
+            }
+        }
+    };
+    if json {
+        println!(
+            "{}",
+            serde_json::json!({"status": "ok", "message": message})
+        );
     } else {
-        return Err(AppError::ProfileNotFound(name).into());
+        ui::print_success(&message);
     }
     Ok(())
 }
 
+fn current_exe() -> String {
+    std::env::current_exe()
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|_| cli::Cli::command().get_name().to_string())
+}
+
 fn handle_completions(shell: Shell) -> Result<()> {
     let mut cmd = cli::Cli::command();
     let bin_name = cmd.get_name().to_string();
     generate(shell, &mut cmd, bin_name, &mut io::stdout());
     Ok(())
 }
+
+fn handle_hook(shell: Shell) -> Result<()> {
+    print!("{}", shell::hook_script(shell, &current_exe())?);
+    Ok(())
+}
+
+fn handle_env(shell: Shell) -> Result<()> {
+    let app_config = config::load_app_config()?;
+    let current_dir = std::env::current_dir()?;
+    let resolved = config::resolve_profile_for_dir(&app_config, &current_dir)
+        .and_then(|name| {
+            config::resolve_profile(&app_config, &name)
+                .ok()
+                .map(|profile| (name, profile))
+        })
+        .map(|(name, profile)| (name, config::profile_entries(&profile)));
+    let profile = resolved
+        .as_ref()
+        .map(|(name, entries)| (name.as_str(), entries.as_slice()));
+    print!("{}", shell::env_script(shell, profile)?);
+    Ok(())
+}
@@ -3,36 +3,45 @@ use clap::Parser;
 use colored::*;
 use std::process::exit;
 
+mod bundle;
 mod cli;
 mod config;
+mod doctor;
 mod domain;
 mod error;
+mod guard;
 mod handler;
+mod migrate;
 mod platform;
+mod shell;
 mod ui;
+mod validate;
 
 fn main() {
-    if let Err(e) = run() {
-        let cli = cli::Cli::try_parse();
-        let is_json_output = match cli {
-            Ok(c) => c.json,
-            Err(_) => false,
-        };
+    let e = match run() {
+        Ok(0) => return,
+        Ok(code) => exit(code),
+        Err(e) => e,
+    };
+    let cli = cli::Cli::try_parse();
+    let (is_json_output, failure_code) = match cli {
+        Ok(c) => (c.json, c.command.failure_code()),
+        Err(_) => (false, 1),
+    };
 
-        if is_json_output {
-            let error_json = serde_json::json!({
-                "status": "error",
-                "message": e.to_string(),
-            });
-            eprintln!("{}", serde_json::to_string(&error_json).unwrap());
-        } else {
-            eprintln!("{}: {}", "Error".red().bold(), e);
-        }
-        exit(1);
+    if is_json_output {
+        let error_json = serde_json::json!({
+            "status": "error",
+            "message": e.to_string(),
+        });
+        eprintln!("{}", serde_json::to_string(&error_json).unwrap());
+    } else {
+        eprintln!("{}: {}", "Error".red().bold(), e);
     }
+    exit(failure_code);
 }
 
-fn run() -> Result<()> {
+fn run() -> Result<i32> {
     let cli = cli::Cli::parse();
     handler::handle_command(cli)
 }
@@ -0,0 +1,216 @@
+use crate::error::AppError;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+pub const CURRENT_SCHEMA_VERSION: u64 = 2;
+
+/// Files written before `schemaVersion` existed.
+const UNVERSIONED: u64 = 1;
+
+/// Works on raw JSON so old layouts never deserialize into `AppConfig`.
+type Migration = fn(&mut Value, &mut Vec<String>);
+
+/// `MIGRATIONS[n]` upgrades version `n + 1` to version `n + 2`.
+const MIGRATIONS: [Migration; 1] = [v1_to_v2];
+
+pub fn schema_version(value: &Value) -> u64 {
+    value
+        .get("schemaVersion")
+        .and_then(Value::as_u64)
+        .unwrap_or(UNVERSIONED)
+        .max(UNVERSIONED)
+}
+
+pub fn migrate(value: &mut Value, notes: &mut Vec<String>) -> Result<u64, AppError> {
+    let version = schema_version(value);
+    if version > CURRENT_SCHEMA_VERSION {
+        return Err(AppError::UnsupportedSchemaVersion {
+            found: version,
+            supported: CURRENT_SCHEMA_VERSION,
+        });
+    }
+    for migration in &MIGRATIONS[(version - UNVERSIONED) as usize..] {
+        migration(value, notes);
+    }
+    if let Some(object) = value.as_object_mut() {
+        object.insert("schemaVersion".to_string(), CURRENT_SCHEMA_VERSION.into());
+    }
+    Ok(version)
+}
+
+fn normalize_gpgsign(value: &str) -> &'static str {
+    match value.to_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => "true",
+        "if-asked" => "if-asked",
+        _ => "false",
+    }
+}
+
+/// Signing policies took over `tag.gpgSign` and `push.gpgSign`. Version 1
+/// never touched them unless told to, so every changed profile is reported.
+fn v1_to_v2(value: &mut Value, notes: &mut Vec<String>) {
+    let Some(profiles) = value.get_mut("profiles").and_then(Value::as_object_mut) else {
+        return;
+    };
+    for (name, profile) in profiles.iter_mut() {
+        let Some(profile) = profile.as_object_mut() else {
+            continue;
+        };
+        let mut removed = BTreeMap::new();
+        if let Some(extra) = profile
+            .get_mut("extraConfig")
+            .and_then(Value::as_object_mut)
+        {
+            extra.retain(|key, value| {
+                let key = key.to_lowercase();
+                if key != "tag.gpgsign" && key != "push.gpgsign" {
+                    return true;
+                }
+                let value = value.as_str().map(normalize_gpgsign).unwrap_or("false");
+                removed.insert(key, value);
+                false
+            });
+        }
+
+        let has_key = profile
+            .get("signingKey")
+            .and_then(Value::as_str)
+            .is_some_and(|key| !key.is_empty());
+        let policy = match profile.get("signingPolicy").and_then(Value::as_str) {
+            Some(policy) => policy.to_string(),
+            None if !has_key && profile.contains_key("extends") => {
+                if !removed.is_empty() {
+                    notes.push(format!(
+                        "Profile '{}' no longer sets {} itself. They now follow the signing policy it inherits.",
+                        name,
+                        removed.keys().cloned().collect::<Vec<_>>().join(" and ")
+                    ));
+                }
+                continue;
+            }
+            None if has_key => "always".to_string(),
+            None => "never".to_string(),
+        };
+        if !removed.is_empty() {
+            profile.insert("signingPolicy".to_string(), policy.clone().into());
+        }
+
+        let changed: Vec<&str> = [
+            ("tag.gpgSign", "true", "false"),
+            ("push.gpgSign", "if-asked", "false"),
+        ]
+        .into_iter()
+        .filter(|(key, always, never)| {
+            let new = match policy.as_str() {
+                "always" => Some(*always),
+                "never" => Some(*never),
+                _ => None,
+            };
+            removed.get(&key.to_lowercase()).copied() != new
+        })
+        .map(|(key, _, _)| key)
+        .collect();
+        if changed.is_empty() {
+            continue;
+        }
+        let verb = if policy == "untouched" {
+            "no longer sets"
+        } else {
+            "now sets"
+        };
+        notes.push(format!(
+            "Profile '{}' {} {} according to its signing policy '{}'. Change it with 'gitup edit {} --signing-policy <policy>'.",
+            name,
+            verb,
+            changed.join(" and "),
+            policy,
+            name
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn run(mut value: Value) -> (Value, u64, Vec<String>) {
+        let mut notes = Vec::new();
+        let version = migrate(&mut value, &mut notes).unwrap();
+        (value, version, notes)
+    }
+
+    #[test]
+    fn unversioned_files_are_upgraded() {
+        let (value, version, notes) = run(json!({
+            "profiles": { "work": { "name": "W", "email": "w@acme.com", "signingKey": "A1B2" } },
+            "currentProfile": "work"
+        }));
+        assert_eq!(version, 1);
+        assert_eq!(value["schemaVersion"], CURRENT_SCHEMA_VERSION);
+        assert_eq!(value["currentProfile"], "work");
+        assert_eq!(value["profiles"]["work"]["signingKey"], "A1B2");
+        assert_eq!(notes.len(), 1);
+        assert!(notes[0].contains("'work' now sets tag.gpgSign and push.gpgSign"));
+    }
+
+    #[test]
+    fn current_files_are_left_alone() {
+        let original = json!({ "schemaVersion": CURRENT_SCHEMA_VERSION, "profiles": {} });
+        let (value, version, notes) = run(original.clone());
+        assert_eq!(version, CURRENT_SCHEMA_VERSION);
+        assert_eq!(value, original);
+        assert!(notes.is_empty());
+    }
+
+    #[test]
+    fn newer_files_are_rejected() {
+        let mut value = json!({ "schemaVersion": CURRENT_SCHEMA_VERSION + 1 });
+        let result = migrate(&mut value, &mut Vec::new());
+        assert!(matches!(
+            result,
+            Err(AppError::UnsupportedSchemaVersion { .. })
+        ));
+    }
+
+    #[test]
+    fn signing_extra_config_becomes_the_policy() {
+        let (value, _, notes) = run(json!({
+            "profiles": {
+                "work": {
+                    "signingKey": "A1B2",
+                    "extraConfig": {
+                        "tag.gpgSign": "yes",
+                        "push.gpgsign": "if-asked",
+                        "core.editor": "vim"
+                    }
+                },
+                "home": {
+                    "extraConfig": { "tag.gpgsign": "false", "push.gpgSign": "false" }
+                }
+            }
+        }));
+        assert_eq!(value["profiles"]["work"]["signingPolicy"], "always");
+        assert_eq!(
+            value["profiles"]["work"]["extraConfig"],
+            json!({ "core.editor": "vim" })
+        );
+        assert_eq!(value["profiles"]["home"]["signingPolicy"], "never");
+        assert_eq!(value["profiles"]["home"]["extraConfig"], json!({}));
+        assert!(notes.is_empty(), "{:?}", notes);
+    }
+
+    #[test]
+    fn conflicting_signing_extra_config_is_reported() {
+        let (value, _, notes) = run(json!({
+            "profiles": {
+                "work": { "signingKey": "A1B2", "extraConfig": { "tag.gpgsign": "false" } },
+                "mine": { "signingPolicy": "untouched", "extraConfig": { "push.gpgsign": "true" } }
+            }
+        }));
+        assert_eq!(value["profiles"]["work"]["signingPolicy"], "always");
+        assert_eq!(notes.len(), 2);
+        assert!(notes[0].contains("'mine' no longer sets push.gpgSign"));
+        assert!(notes[1].contains("'work' now sets tag.gpgSign and push.gpgSign"));
+    }
+}
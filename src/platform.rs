@@ -1,4 +1,6 @@
+use crate::domain::SigningFormat;
 use crate::error::AppError;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 
 pub fn is_git_installed() -> bool {
@@ -10,6 +12,15 @@ pub fn is_git_installed() -> bool {
         .is_ok_and(|s| s.success())
 }
 
+pub fn is_inside_git_repository() -> bool {
+    Command::new("git")
+        .args(["rev-parse", "--git-dir"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
 pub fn get_install_command() -> Result<String, AppError> {
     if cfg!(target_os = "linux") {
         if Command::new("which")
@@ -68,3 +79,101 @@ pub fn run_command(cmd: &str, args: &[&str]) -> Result<String, AppError> {
         })
     }
 }
+
+pub fn expand_home(path: &Path) -> PathBuf {
+    match path.strip_prefix("~") {
+        Ok(rest) => match dirs::home_dir() {
+            Some(home) => home.join(rest),
+            None => path.to_path_buf(),
+        },
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+pub fn system_config_dir() -> PathBuf {
+    if cfg!(target_os = "windows") {
+        std::env::var_os("ProgramData")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(r"C:\ProgramData"))
+            .join("gitup")
+    } else {
+        PathBuf::from("/etc/gitup")
+    }
+}
+
+pub fn create_private_file(path: &Path) -> std::io::Result<std::fs::File> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+pub fn make_executable(path: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+pub fn file_mode(path: &Path) -> Option<u32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::metadata(path)
+            .ok()
+            .map(|metadata| metadata.permissions().mode() & 0o777)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+pub fn check_private_key(path: &Path) -> Result<(), String> {
+    let path = expand_home(path);
+    if !path.is_file() {
+        return Err(format!("'{}' does not exist.", path.display()));
+    }
+    match file_mode(&path) {
+        Some(mode) if mode & 0o077 != 0 => Err(format!(
+            "'{}' is accessible by other users (mode {:o}).",
+            path.display(),
+            mode
+        )),
+        _ => Ok(()),
+    }
+}
+
+pub fn check_signing_key(key: &str, format: SigningFormat) -> Result<(), String> {
+    match format {
+        SigningFormat::Ssh => {
+            let is_literal = ["key::", "ssh-", "ecdsa-", "sk-"]
+                .iter()
+                .any(|prefix| key.starts_with(prefix));
+            let path = expand_home(Path::new(key));
+            if is_literal || path.is_file() {
+                Ok(())
+            } else {
+                Err(format!(
+                    "the SSH key file '{}' does not exist",
+                    path.display()
+                ))
+            }
+        }
+        SigningFormat::X509 => run_command("gpgsm", &["--list-secret-keys", key])
+            .map(|_| ())
+            .map_err(|_| "gpgsm has no matching X.509 secret key".to_string()),
+        SigningFormat::Openpgp => run_command("gpg", &["--list-secret-keys", key])
+            .map(|_| ())
+            .map_err(|_| "it is not in the GPG secret keyring".to_string()),
+    }
+}
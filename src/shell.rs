@@ -0,0 +1,111 @@
+use crate::error::AppError;
+use clap_complete::Shell;
+
+/// Set alongside the `GIT_CONFIG_*` variables so only ours are cleared.
+const PROFILE_VAR: &str = "GITUP_SHELL_PROFILE";
+
+fn quote(shell: Shell, value: &str) -> String {
+    match shell {
+        Shell::Fish => format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'")),
+        _ => format!("'{}'", value.replace('\'', "'\\''")),
+    }
+}
+
+fn ensure_supported(shell: Shell) -> Result<(), AppError> {
+    match shell {
+        Shell::Bash | Shell::Zsh | Shell::Fish => Ok(()),
+        other => Err(AppError::UnsupportedShell(other.to_string())),
+    }
+}
+
+pub fn hook_script(shell: Shell, exe: &str) -> Result<String, AppError> {
+    ensure_supported(shell)?;
+    let exe = quote(shell, exe);
+    let script = match shell {
+        Shell::Bash => format!(
+            r#"_gitup_hook() {{
+  local previous_exit_status=$?
+  if [[ "${{_GITUP_LAST_PWD:-}}" != "$PWD" ]]; then
+    _GITUP_LAST_PWD="$PWD"
+    eval "$({exe} env bash)"
+  fi
+  return $previous_exit_status
+}}
+if [[ ";${{PROMPT_COMMAND[*]:-}};" != *";_gitup_hook;"* ]]; then
+  PROMPT_COMMAND="_gitup_hook${{PROMPT_COMMAND:+;$PROMPT_COMMAND}}"
+fi
+"#
+        ),
+        Shell::Zsh => format!(
+            r#"_gitup_hook() {{
+  eval "$({exe} env zsh)"
+}}
+autoload -Uz add-zsh-hook
+add-zsh-hook chpwd _gitup_hook
+_gitup_hook
+"#
+        ),
+        _ => format!(
+            r#"function __gitup_hook --on-variable PWD
+  {exe} env fish | source
+end
+__gitup_hook
+"#
+        ),
+    };
+    Ok(script)
+}
+
+fn export(shell: Shell, name: &str, value: &str) -> String {
+    match shell {
+        Shell::Fish => format!("set -gx {} {};\n", name, quote(shell, value)),
+        _ => format!("export {}={};\n", name, quote(shell, value)),
+    }
+}
+
+fn unset(shell: Shell, name: &str) -> String {
+    match shell {
+        Shell::Fish => format!("set -e {};\n", name),
+        _ => format!("unset {};\n", name),
+    }
+}
+
+pub fn env_script(
+    shell: Shell,
+    profile: Option<(&str, &[(String, String)])>,
+) -> Result<String, AppError> {
+    ensure_supported(shell)?;
+    let mut script = String::new();
+
+    if std::env::var_os(PROFILE_VAR).is_some() {
+        let previous_count: usize = std::env::var("GIT_CONFIG_COUNT")
+            .ok()
+            .and_then(|count| count.parse().ok())
+            .unwrap_or(0);
+        for index in 0..previous_count {
+            script.push_str(&unset(shell, &format!("GIT_CONFIG_KEY_{}", index)));
+            script.push_str(&unset(shell, &format!("GIT_CONFIG_VALUE_{}", index)));
+        }
+        script.push_str(&unset(shell, "GIT_CONFIG_COUNT"));
+        script.push_str(&unset(shell, PROFILE_VAR));
+    }
+
+    if let Some((name, entries)) = profile {
+        for (index, (key, value)) in entries.iter().enumerate() {
+            script.push_str(&export(shell, &format!("GIT_CONFIG_KEY_{}", index), key));
+            script.push_str(&export(
+                shell,
+                &format!("GIT_CONFIG_VALUE_{}", index),
+                value,
+            ));
+        }
+        script.push_str(&export(
+            shell,
+            "GIT_CONFIG_COUNT",
+            &entries.len().to_string(),
+        ));
+        script.push_str(&export(shell, PROFILE_VAR, name));
+    }
+
+    Ok(script)
+}
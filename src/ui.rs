@@ -1,8 +1,14 @@
-use crate::domain::{AppConfig, GitUserConfig};
+use crate::domain::{
+    AppConfig, CheckStatus, ConfigSource, Diagnostic, FieldComparison, GitUserConfig, HistoryEntry,
+    ImportAction, ImportChange, PlannedChange, ProfileStatus,
+};
 use crate::error::AppError;
+use crate::platform;
 use colored::*;
 use dialoguer::theme::ColorfulTheme;
 use dialoguer::{Confirm, Input, Select};
+use std::collections::HashMap;
+use std::path::Path;
 
 pub fn confirm(prompt: &str, default: bool) -> Result<bool, AppError> {
     Confirm::with_theme(&ColorfulTheme::default())
@@ -25,7 +31,12 @@ pub fn prompt_for_input(prompt: &str, default: Option<&str>) -> Result<String, A
         .map_err(|_| AppError::OperationCancelled)
 }
 
-pub fn prompt_for_optional_input(prompt: &str, default: Option<&str>) -> Result<String, AppError> {
+/// Empty input is passed to `validate` too, which decides if it is allowed.
+pub fn prompt_for_valid_input(
+    prompt: &str,
+    default: Option<&str>,
+    validate: impl Fn(&str) -> Result<String, AppError>,
+) -> Result<String, AppError> {
     let theme = ColorfulTheme::default();
     let mut builder = Input::with_theme(&theme).with_prompt(prompt);
 
@@ -33,10 +44,12 @@ pub fn prompt_for_optional_input(prompt: &str, default: Option<&str>) -> Result<
         builder = builder.default(val.to_string());
     }
 
-    builder
+    let input: String = builder
         .allow_empty(true)
+        .validate_with(|input: &String| validate(input).map(|_| ()).map_err(|e| e.to_string()))
         .interact_text()
-        .map_err(|_| AppError::OperationCancelled)
+        .map_err(|_| AppError::OperationCancelled)?;
+    validate(&input)
 }
 
 pub fn select_profile(profiles: &[String]) -> Result<String, AppError> {
@@ -54,33 +67,184 @@ pub fn select_profile(profiles: &[String]) -> Result<String, AppError> {
     Ok(profiles[selection].clone())
 }
 
+fn format_origin(origins: &HashMap<String, String>, key: &str) -> String {
+    match origins.get(key) {
+        Some(scope) => format!(" [{}]", scope).dimmed().to_string(),
+        None => String::new(),
+    }
+}
+
 pub fn print_status(config: &GitUserConfig, app_config: Option<&AppConfig>) {
+    print_scoped_status(config, &HashMap::new(), app_config);
+}
+
+pub fn print_scoped_status(
+    config: &GitUserConfig,
+    origins: &HashMap<String, String>,
+    app_config: Option<&AppConfig>,
+) {
     println!("{}", "Git Configuration Status".bold().underline());
+    print_config_fields(config, origins);
+    if let Some(ac) = app_config {
+        if let Some(profile) = &ac.current_profile {
+            println!(
+                "  {:<12}: {} ({})",
+                "Profile".green(),
+                profile,
+                "active".cyan()
+            );
+        }
+    }
+}
+
+pub fn print_profile_status(status: &ProfileStatus) {
+    match status {
+        ProfileStatus::Active { profile } => println!(
+            "  {:<12}: {} ({})",
+            "Profile".green(),
+            profile,
+            "active".cyan()
+        ),
+        ProfileStatus::Modified { profile, changes } => {
+            println!(
+                "  {:<12}: {} ({})",
+                "Profile".yellow(),
+                profile,
+                "active, modified".yellow()
+            );
+            let value = |value: &Option<String>| match value {
+                Some(value) => format!("'{}'", value),
+                None => "not set".to_string(),
+            };
+            for change in changes {
+                println!(
+                    "    {}: {} {} {}",
+                    change.field,
+                    value(&change.expected).dimmed(),
+                    "->".dimmed(),
+                    value(&change.actual)
+                );
+            }
+        }
+        ProfileStatus::Unmatched => {
+            println!(
+                "  {:<12}: {}",
+                "Profile".yellow(),
+                "no matching profile".yellow()
+            )
+        }
+    }
+}
+
+pub fn print_profile(
+    name: &str,
+    extends: Option<&str>,
+    config: &GitUserConfig,
+    origins: &HashMap<String, String>,
+) {
+    println!("{}", format!("Profile '{}'", name).bold().underline());
+    if let Some(base) = extends {
+        println!("  {:<12}: {}", "Extends".green(), base.cyan());
+    }
+    print_config_fields(config, origins);
+}
+
+fn print_config_fields(config: &GitUserConfig, origins: &HashMap<String, String>) {
     match &config.name {
-        Some(name) if !name.is_empty() => println!("  {:<12}: {}", "Name".green(), name),
+        Some(name) if !name.is_empty() => println!(
+            "  {:<12}: {}{}",
+            "Name".green(),
+            name,
+            format_origin(origins, "user.name")
+        ),
         _ => println!("  {:<12}: Not Set", "Name".yellow()),
     }
     match &config.email {
-        Some(email) if !email.is_empty() => println!("  {:<12}: {}", "Email".green(), email),
+        Some(email) if !email.is_empty() => println!(
+            "  {:<12}: {}{}",
+            "Email".green(),
+            email,
+            format_origin(origins, "user.email")
+        ),
         _ => println!("  {:<12}: Not Set", "Email".yellow()),
     }
-    match &config.signing_key {
-        Some(key) if !key.is_empty() => println!("  {:<12}: {}", "Signing Key".green(), key),
-        _ => (),
+    if let (Some(key), Some(format)) = (&config.signing_key, config.effective_signing_format()) {
+        println!(
+            "  {:<12}: {} ({}){}",
+            "Signing Key".green(),
+            key,
+            format.as_str(),
+            format_origin(origins, "user.signingkey")
+        );
     }
-    if let Some(ac) = app_config {
-        if let Some(profile) = &ac.current_profile {
+    if let Some(policy) = config.signing_policy {
+        println!(
+            "  {:<12}: {}{}",
+            "Signing".green(),
+            policy.as_str(),
+            format_origin(origins, "commit.gpgsign")
+        );
+    }
+    if let Some(path) = config.ssh_key.as_deref().filter(|path| !path.is_empty()) {
+        match platform::check_private_key(Path::new(path)) {
+            Ok(()) => println!(
+                "  {:<12}: {}{}",
+                "SSH Key".green(),
+                path,
+                format_origin(origins, "core.sshCommand")
+            ),
+            Err(problem) => println!(
+                "  {:<12}: {}{} ({})",
+                "SSH Key".yellow(),
+                path,
+                format_origin(origins, "core.sshCommand"),
+                problem.yellow()
+            ),
+        }
+    }
+    if !config.extra_config.is_empty() {
+        println!("  {}:", "Extra Config".green());
+        for (key, value) in &config.extra_config {
+            println!("    {} = {}{}", key, value, format_origin(origins, key));
+        }
+    }
+}
+
+pub fn print_comparison(left: &str, right: Option<&str>, comparison: &[FieldComparison]) {
+    let right_label = match right {
+        Some(name) => format!("profile '{}'", name),
+        None => "the live config".to_string(),
+    };
+    println!(
+        "{}",
+        format!("Profile '{}' vs {}", left, right_label)
+            .bold()
+            .underline()
+    );
+    let value = |value: &Option<String>| match value {
+        Some(value) => value.clone(),
+        None => "Not Set".to_string(),
+    };
+    for row in comparison {
+        if row.differs {
             println!(
-                "  {:<12}: {} ({})",
-                "Profile".green(),
-                profile,
-                "active".cyan()
+                "  {:<16}: {} {} {}",
+                row.field.yellow(),
+                value(&row.left).red(),
+                "->".dimmed(),
+                value(&row.right).green()
             );
+        } else {
+            println!("  {:<16}: {}", row.field.green(), value(&row.left));
         }
     }
+    match comparison.iter().filter(|row| row.differs).count() {
+        0 => println!("\n  No differences."),
+        count => println!("\n  {} field(s) differ.", count),
+    }
 }
 
-pub fn print_profiles(config: &AppConfig) {
+pub fn print_profiles(config: &AppConfig, status: &ProfileStatus) {
     println!("{}", "Saved Profiles".bold().underline());
     if config.profiles.is_empty() {
         println!("  No profiles saved.");
@@ -90,10 +254,33 @@ pub fn print_profiles(config: &AppConfig) {
     sorted_profiles.sort();
 
     for name in sorted_profiles {
-        if Some(name) == config.current_profile.as_ref() {
-            println!("  - {} ({})", name.bold(), "active".cyan());
+        let mut extends = match &config.profiles[name].extends {
+            Some(base) => format!(" (extends {})", base).dimmed().to_string(),
+            None => String::new(),
+        };
+        if config.system_profiles.contains(name) {
+            extends.push_str(&" [system]".dimmed().to_string());
+        }
+        let marker = match status {
+            ProfileStatus::Active { profile } if profile == name => Some("active".cyan()),
+            ProfileStatus::Modified { profile, .. } if profile == name => {
+                Some("active, modified".yellow())
+            }
+            _ => None,
+        };
+        if let Some(marker) = marker {
+            println!("  - {}{} ({})", name.bold(), extends, marker);
         } else {
-            println!("  - {}", name);
+            println!("  - {}{}", name, extends);
+        }
+    }
+
+    if !config.bindings.is_empty() {
+        println!("\n{}", "Directory Bindings".bold().underline());
+        let mut sorted_bindings: Vec<_> = config.bindings.iter().collect();
+        sorted_bindings.sort();
+        for (dir, profile) in sorted_bindings {
+            println!("  {}/ -> {}", dir, profile.cyan());
         }
     }
 }
@@ -104,20 +291,342 @@ pub fn print_json_status(config: &GitUserConfig, app_config: &AppConfig) {
         "data": {
             "isGitInstalled": true,
             "config": config,
-            "activeProfile": app_config.current_profile
+            "activeProfile": app_config.current_profile,
+            "previousProfile": app_config.previous_profile
+        }
+    });
+    println!("{}", serde_json::to_string_pretty(&json).unwrap());
+}
+
+pub fn print_json_scoped_status(
+    config: &GitUserConfig,
+    origins: &HashMap<String, String>,
+    app_config: &AppConfig,
+    status: &ProfileStatus,
+    local_profile: Option<&str>,
+) {
+    let json = serde_json::json!({
+        "status": "ok",
+        "data": {
+            "isGitInstalled": true,
+            "config": config,
+            "origins": origins,
+            "activeProfile": app_config.current_profile,
+            "previousProfile": app_config.previous_profile,
+            "profileStatus": status,
+            "localProfile": local_profile
+        }
+    });
+    println!("{}", serde_json::to_string_pretty(&json).unwrap());
+}
+
+pub fn print_json_profile(name: &str, config: &GitUserConfig, origins: &HashMap<String, String>) {
+    let json = serde_json::json!({
+        "status": "ok",
+        "data": {
+            "name": name,
+            "profile": config,
+            "origins": origins
         }
     });
     println!("{}", serde_json::to_string_pretty(&json).unwrap());
 }
 
-pub fn print_json_profiles(config: &AppConfig) {
+pub fn print_local_profile(name: &str) {
+    println!(
+        "  {:<12}: {} ({})",
+        "Profile".green(),
+        name,
+        "this repository".cyan()
+    );
+}
+
+pub fn print_paths(
+    config_file: &Path,
+    source: ConfigSource,
+    lock_file: &Path,
+    includes_dir: &Path,
+    profile_store: Option<(&Path, &Path)>,
+    system_dir: &Path,
+    git_config: &Path,
+) {
+    println!("{}", "GitUp Paths".bold().underline());
+    println!(
+        "  {:<12}: {} {}",
+        "Config File".green(),
+        config_file.display(),
+        format!("({})", source.describe()).dimmed()
+    );
+    println!("  {:<12}: {}", "Lock File".green(), lock_file.display());
+    println!("  {:<12}: {}", "Includes".green(), includes_dir.display());
+    if let Some((profiles_dir, state_file)) = profile_store {
+        println!("  {:<12}: {}", "Profiles".green(), profiles_dir.display());
+        println!("  {:<12}: {}", "State File".green(), state_file.display());
+    }
+    println!("  {:<12}: {}", "System".green(), system_dir.display());
+    println!("  {:<12}: {}", "Git Config".green(), git_config.display());
+}
+
+pub fn print_json_profiles(config: &AppConfig, status: &ProfileStatus) {
+    let mut data = serde_json::json!(config);
+    data["profileStatus"] = serde_json::json!(status);
+    data["systemProfiles"] = serde_json::json!(config.system_profiles);
+    data["shadowedProfiles"] = serde_json::json!(config.shadowed_profiles);
+    let json = serde_json::json!({
+        "status": "ok",
+        "data": data,
+    });
+    println!("{}", serde_json::to_string_pretty(&json).unwrap());
+}
+
+pub fn print_import_preview(changes: &[ImportChange]) {
+    println!("{}", "Import Preview".bold().underline());
+    if changes.is_empty() {
+        println!("  The bundle contains no profiles.");
+        return;
+    }
+    for change in changes {
+        match change.action {
+            ImportAction::Add => println!("  {} {} (new)", "+".green(), change.profile),
+            ImportAction::Overwrite => println!(
+                "  {} {} (overwrites the existing profile)",
+                "~".yellow(),
+                change.profile
+            ),
+            ImportAction::Rename => println!(
+                "  {} {} -> {} (name already taken)",
+                ">".cyan(),
+                change.profile,
+                change.target
+            ),
+            ImportAction::Skip => println!(
+                "  {} {} (skipped, name already taken)",
+                "-".dimmed(),
+                change.profile
+            ),
+            ImportAction::Unchanged => {
+                println!("  {} {} (unchanged)", "=".dimmed(), change.profile)
+            }
+        }
+    }
+}
+
+pub fn print_history(history: &[HistoryEntry]) {
+    println!("{}", "Identity History".bold().underline());
+    if history.is_empty() {
+        println!("  No changes recorded yet.");
+        return;
+    }
+    for (index, entry) in history.iter().enumerate() {
+        let time = std::time::UNIX_EPOCH + std::time::Duration::from_secs(entry.timestamp);
+        let identity = format!(
+            "{} <{}>",
+            entry.config.name.as_deref().unwrap_or("no name"),
+            entry.config.email.as_deref().unwrap_or("no email")
+        );
+        let profile = match &entry.profile {
+            Some(profile) => format!(" ({})", profile).cyan().to_string(),
+            None => String::new(),
+        };
+        println!(
+            "  {:>2}  {}  {:<16} {}{}",
+            index + 1,
+            humantime::format_rfc3339_seconds(time).to_string().dimmed(),
+            entry.command,
+            identity,
+            profile
+        );
+    }
+}
+
+fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<(char, &'a str)> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push((' ', old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(('-', old[i]));
+            i += 1;
+        } else {
+            lines.push(('+', new[j]));
+            j += 1;
+        }
+    }
+    lines
+}
+
+pub fn print_planned_changes(changes: &[PlannedChange]) {
+    println!("{}", "Dry Run".bold().underline());
+    if changes.is_empty() {
+        println!("  No changes would be made.");
+        return;
+    }
+    let value = |value: &Option<String>| match value {
+        Some(value) => format!("'{}'", value),
+        None => "not set".to_string(),
+    };
+    for change in changes {
+        match change {
+            PlannedChange::GitConfig {
+                scope,
+                key,
+                old,
+                new,
+            } => println!(
+                "  {} {}: {} {} {}",
+                format!("[{}]", scope).dimmed(),
+                key,
+                value(old).dimmed(),
+                "->".dimmed(),
+                value(new)
+            ),
+            PlannedChange::RemoveSection { scope, section } => println!(
+                "  {} {} {}",
+                format!("[{}]", scope).dimmed(),
+                section,
+                "(section removed)".yellow()
+            ),
+            PlannedChange::File { path, old, new } => {
+                let state = match (old, new) {
+                    (None, _) => "new file",
+                    (_, None) => "removed",
+                    _ => "modified",
+                };
+                println!("  {} ({})", path.display(), state.cyan());
+                let old = old.as_deref().unwrap_or("");
+                let new = new.as_deref().unwrap_or("");
+                for (marker, line) in diff_lines(old, new) {
+                    match marker {
+                        '+' => println!("    {}", format!("+ {}", line).green()),
+                        '-' => println!("    {}", format!("- {}", line).red()),
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+    println!("\n  Nothing was changed. Run the command without --dry-run to apply it.");
+}
+
+pub fn print_rules(config: &AppConfig) {
+    println!("{}", "Remote Rules".bold().underline());
+    if config.rules.is_empty() {
+        println!("  No rules defined.");
+        return;
+    }
+    let mut sorted_rules: Vec<_> = config.rules.iter().collect();
+    sorted_rules.sort();
+    for (pattern, profile) in sorted_rules {
+        println!("  {} -> {}", pattern, profile.cyan());
+    }
+}
+
+pub fn print_json_rules(config: &AppConfig) {
     let json = serde_json::json!({
         "status": "ok",
-        "data": config,
+        "data": { "rules": config.rules },
     });
     println!("{}", serde_json::to_string_pretty(&json).unwrap());
 }
 
+pub fn print_diagnostics(diagnostics: &[Diagnostic]) {
+    println!("{}", "GitUp Doctor".bold().underline());
+    for diagnostic in diagnostics {
+        let label = match diagnostic.status {
+            CheckStatus::Pass => "[PASS]".green(),
+            CheckStatus::Warn => "[WARN]".yellow(),
+            CheckStatus::Fail => "[FAIL]".red(),
+        };
+        println!(
+            "  {} {:<22} {}",
+            label.bold(),
+            diagnostic.name,
+            diagnostic.message
+        );
+        if let Some(hint) = &diagnostic.hint {
+            println!("         {} {}", "hint:".cyan(), hint);
+        }
+    }
+
+    let count = |status| diagnostics.iter().filter(|d| d.status == status).count();
+    println!(
+        "\n  {} passed, {} warning(s), {} failed",
+        count(CheckStatus::Pass),
+        count(CheckStatus::Warn),
+        count(CheckStatus::Fail)
+    );
+}
+
+pub fn print_json_diagnostics(diagnostics: &[Diagnostic]) {
+    let failed = diagnostics.iter().any(|d| d.status == CheckStatus::Fail);
+    let json = serde_json::json!({
+        "status": if failed { "error" } else { "ok" },
+        "data": { "checks": diagnostics },
+    });
+    println!("{}", serde_json::to_string_pretty(&json).unwrap());
+}
+
+pub fn print_warning(message: &str) {
+    eprintln!("{} {}", "Warning:".yellow().bold(), message);
+}
+
 pub fn print_success(message: &str) {
     println!("{} {}", "Success:".green().bold(), message);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unchanged_files_have_no_markers() {
+        let text = "a\nb\n";
+        assert_eq!(diff_lines(text, text), vec![(' ', "a"), (' ', "b")]);
+    }
+
+    #[test]
+    fn changed_lines_are_removed_then_added() {
+        assert_eq!(
+            diff_lines(
+                "name = a\nemail = a@x.org\nkey = 1\n",
+                "name = a\nemail = b@x.org\nkey = 1\n"
+            ),
+            vec![
+                (' ', "name = a"),
+                ('-', "email = a@x.org"),
+                ('+', "email = b@x.org"),
+                (' ', "key = 1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn new_and_deleted_files_diff_against_nothing() {
+        assert_eq!(diff_lines("", "a\nb"), vec![('+', "a"), ('+', "b")]);
+        assert_eq!(diff_lines("a\nb", ""), vec![('-', "a"), ('-', "b")]);
+        assert!(diff_lines("", "").is_empty());
+    }
+
+    #[test]
+    fn inserted_lines_keep_the_rest_in_common() {
+        assert_eq!(
+            diff_lines("a\nc\nd\n", "a\nb\nc\ne\n"),
+            vec![(' ', "a"), ('+', "b"), (' ', "c"), ('-', "d"), ('+', "e"),]
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use crate::domain::{GitUserConfig, SigningFormat};
+use crate::error::AppError;
+use crate::{config, platform};
+use std::fs;
+use std::path::Path;
+
+/// Git strips `<` and `>` from names, so they are rejected rather than lost.
+pub fn name(value: &str) -> Result<String, AppError> {
+    let normalized = value.split_whitespace().collect::<Vec<_>>().join(" ");
+    let reason = if normalized.is_empty() {
+        "it is empty"
+    } else if normalized.contains(['<', '>']) {
+        "it contains '<' or '>'"
+    } else {
+        return Ok(normalized);
+    };
+    Err(AppError::InvalidName {
+        name: value.to_string(),
+        reason,
+    })
+}
+
+fn is_valid_domain(domain: &str) -> bool {
+    let labels: Vec<&str> = domain.split('.').collect();
+    labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+        })
+}
+
+/// Catches typos, not every address RFC 5322 allows.
+pub fn email(value: &str) -> Result<String, AppError> {
+    let email = value.trim();
+    let reason = if email.is_empty() {
+        "it is empty"
+    } else if email
+        .chars()
+        .any(|c| c.is_whitespace() || c.is_control() || c == '<' || c == '>')
+    {
+        "it contains spaces or angle brackets"
+    } else {
+        match email.split_once('@') {
+            Some((local, domain)) if !local.is_empty() && is_valid_domain(domain) => {
+                return Ok(email.to_string())
+            }
+            _ => "it is not of the form name@example.com",
+        }
+    };
+    Err(AppError::InvalidEmail {
+        email: value.to_string(),
+        reason,
+    })
+}
+
+pub fn signing_key(value: &str) -> Result<String, AppError> {
+    let key = value.trim();
+    platform::check_signing_key(key, SigningFormat::detect(key)).map_err(|reason| {
+        AppError::InvalidSigningKey {
+            key: key.to_string(),
+            reason,
+        }
+    })?;
+    Ok(key.to_string())
+}
+
+/// `config::validate_signing_key` for commands that can skip it with `--force`.
+pub fn signing_config(config: &GitUserConfig) -> Result<(), AppError> {
+    config::validate_signing_key(config).map_err(|e| match e {
+        AppError::SigningKeyNotFound { key, reason } => AppError::InvalidSigningKey { key, reason },
+        e => e,
+    })
+}
+
+pub fn ssh_key(value: &str) -> Result<String, AppError> {
+    let path = value.trim();
+    fs::File::open(platform::expand_home(Path::new(path)))
+        .map(|_| path.to_string())
+        .map_err(|e| AppError::SshKeyUnreadable {
+            path: path.to_string(),
+            reason: e.to_string(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_normalized() {
+        assert_eq!(name("  Ada   Lovelace ").unwrap(), "Ada Lovelace");
+        assert_eq!(name("Ada\tLovelace").unwrap(), "Ada Lovelace");
+        assert_eq!(
+            name("Jürgen O'Brien-Smith").unwrap(),
+            "Jürgen O'Brien-Smith"
+        );
+    }
+
+    #[test]
+    fn invalid_names_are_rejected() {
+        for value in ["", "   ", "Ada <ada@x.org>", "a>b"] {
+            assert!(
+                matches!(name(value), Err(AppError::InvalidName { .. })),
+                "{:?}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn emails_are_trimmed() {
+        assert_eq!(email(" ada@example.com\n").unwrap(), "ada@example.com");
+        assert_eq!(
+            email("ada+git@mail.example-corp.co.uk").unwrap(),
+            "ada+git@mail.example-corp.co.uk"
+        );
+        assert_eq!(email("ada@bücher.de").unwrap(), "ada@bücher.de");
+    }
+
+    #[test]
+    fn invalid_emails_are_rejected() {
+        for value in [
+            "",
+            "ada",
+            "@example.com",
+            "ada@",
+            "ada@localhost",
+            "ada@example..com",
+            "ada@-example.com",
+            "ada@example.com-",
+            "ada@exa_mple.com",
+            "ada lovelace@example.com",
+            "<ada@example.com>",
+            "ada@example.com@",
+        ] {
+            assert!(
+                matches!(email(value), Err(AppError::InvalidEmail { .. })),
+                "{:?}",
+                value
+            );
+        }
+    }
+}